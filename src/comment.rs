@@ -0,0 +1,341 @@
+//! Structured commands embedded in comments, like `[%clk 0:05:03]`, `[%eval 0.24]` and `[%pv e4 e5 Nf3]`.
+
+use crate::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// An engine evaluation from an `[%eval]` comment command, from the first player's point of view.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Eval {
+    /// An evaluation in hundredths of a pawn, written in pawns like `0.24`
+    Centipawns(i32),
+    /// A forced mate in this many moves, written like `#3`, or `#-3` if the second player mates
+    Mate(i32),
+}
+
+impl Eval {
+    /// The value in centipawns that [`centipawns`] gives forced mates, with the sign of the mating player
+    ///
+    /// [`centipawns`]: #method.centipawns
+    pub const MATE_CENTIPAWNS: i32 = 10_000;
+
+    /// Returns the evaluation in centipawns, bounded by `MATE_CENTIPAWNS` in either direction.
+    ///
+    /// Forced mates are `MATE_CENTIPAWNS` if the first player mates, and `-MATE_CENTIPAWNS` if the second player mates.
+    pub fn centipawns(self) -> i32 {
+        match self {
+            Eval::Centipawns(centipawns) => {
+                centipawns.clamp(-Self::MATE_CENTIPAWNS, Self::MATE_CENTIPAWNS)
+            }
+            Eval::Mate(moves) if moves < 0 => -Self::MATE_CENTIPAWNS,
+            Eval::Mate(_) => Self::MATE_CENTIPAWNS,
+        }
+    }
+}
+
+impl FromStr for Eval {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Error> {
+        let error = || Error::new_parse_error(format!("Invalid evaluation \"{}\"", input));
+        match input.strip_prefix('#') {
+            Some(moves) => moves.parse().map(Eval::Mate).map_err(|_| error()),
+            None => {
+                let pawns: f64 = input.parse().map_err(|_| error())?;
+                let centipawns = (pawns * 100.0).round();
+                if centipawns.is_finite() && centipawns.abs() <= i32::MAX as f64 {
+                    Ok(Eval::Centipawns(centipawns as i32))
+                } else {
+                    Err(error())
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Eval {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Eval::Centipawns(centipawns) => {
+                let sign = if *centipawns < 0 { "-" } else { "" };
+                let centipawns = centipawns.unsigned_abs();
+                write!(fmt, "{}{}.{:02}", sign, centipawns / 100, centipawns % 100)
+            }
+            Eval::Mate(moves) => write!(fmt, "#{}", moves),
+        }
+    }
+}
+
+/// The `[%clk]` and `[%eval]` commands of a comment, as written by sites like Lichess and Chess.com,
+/// and the `[%depth]` and `[%pv]` commands written by some engines.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommentCommands {
+    /// The player's remaining time after the move, from `[%clk 0:05:03]`
+    pub clock: Option<Duration>,
+    /// The engine evaluation after the move, from `[%eval 0.24]`
+    pub eval: Option<Eval>,
+    /// The search depth of the evaluation, from `[%depth 20]`
+    pub depth: Option<u32>,
+    /// The engine's principal variation, from `[%pv e4 e5 Nf3]`.
+    /// The moves are kept as written, since resolving them requires the position.
+    pub pv: Vec<String>,
+}
+
+impl CommentCommands {
+    /// Returns whether there are no commands
+    pub fn is_empty(&self) -> bool {
+        self.clock.is_none() && self.eval.is_none() && self.depth.is_none() && self.pv.is_empty()
+    }
+
+    /// Extracts the `%clk`, `%eval`, `%depth` and `%pv` commands from a comment, returning them and the rest of the comment.
+    ///
+    /// The argument may directly follow the command name, like `[%clk0:05:03]`.
+    /// `%pv` takes any number of moves separated by whitespace, and the other commands take a single argument.
+    /// Other commands, and commands that fail to parse, are left in the text.
+    /// Each extracted command is removed along with one whitespace character that separates it from the text,
+    /// and the rest of the comment is kept verbatim. If only whitespace remains, the text is empty.
+    pub fn parse(comment: &str) -> (CommentCommands, String) {
+        let mut commands = CommentCommands::default();
+        let mut text = String::new();
+        let mut rest = comment;
+
+        while let Some(start) = rest.find("[%") {
+            let end = match rest[start..].find(']') {
+                Some(i) => start + i,
+                None => break,
+            };
+            let is_extracted = match split_command(&rest[start + 2..end]) {
+                Some(("clk", clock)) => parse_clock(clock)
+                    .map(|clock| commands.clock = Some(clock))
+                    .is_some(),
+                Some(("eval", eval)) => eval.parse().map(|eval| commands.eval = Some(eval)).is_ok(),
+                Some(("depth", depth)) => depth
+                    .parse()
+                    .map(|depth| commands.depth = Some(depth))
+                    .is_ok(),
+                Some(("pv", moves)) => {
+                    commands.pv = moves.split_whitespace().map(str::to_string).collect();
+                    true
+                }
+                _ => false,
+            };
+            if is_extracted {
+                text.push_str(&rest[..start]);
+                rest = &rest[end + 1..];
+                match rest.chars().next() {
+                    Some(c) if c.is_whitespace() => rest = &rest[c.len_utf8()..],
+                    Some(_) => (),
+                    None => {
+                        if text.ends_with(char::is_whitespace) {
+                            text.pop();
+                        }
+                    }
+                }
+            } else {
+                text.push_str(&rest[..=end]);
+                rest = &rest[end + 1..];
+            }
+        }
+        text.push_str(rest);
+
+        if !commands.is_empty() && text.trim().is_empty() {
+            text.clear();
+        }
+        (commands, text)
+    }
+
+    /// Returns the text of a comment with the commands in canonical form, followed by `text`.
+    pub fn to_comment(&self, text: Option<&str>) -> String {
+        let mut parts = vec![];
+        if let Some(eval) = self.eval {
+            parts.push(format!("[%eval {}]", eval));
+        }
+        if let Some(depth) = self.depth {
+            parts.push(format!("[%depth {}]", depth));
+        }
+        if !self.pv.is_empty() {
+            parts.push(format!("[%pv {}]", self.pv.join(" ")));
+        }
+        if let Some(clock) = self.clock {
+            parts.push(format!("[%clk {}]", ClockDisplay(clock)));
+        }
+        parts.extend(text.map(str::to_string));
+        parts.join(" ")
+    }
+}
+
+/// The names of the commands that are extracted from comments
+const COMMAND_NAMES: &[&str] = &["clk", "eval", "depth", "pv"];
+
+/// Splits a command like `clk 0:05:03` into its name and its arguments.
+///
+/// Known command names may be followed directly by the argument, like `clk0:05:03`.
+/// Only `pv` may have more than one argument.
+fn split_command(command: &str) -> Option<(&str, &str)> {
+    let command = command.trim();
+    let (name, argument) = match command.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim_start()),
+        None => {
+            let name = COMMAND_NAMES
+                .iter()
+                .find(|name| command.starts_with(*name))?;
+            (*name, &command[name.len()..])
+        }
+    };
+    if argument.is_empty() || (name != "pv" && argument.contains(char::is_whitespace)) {
+        None
+    } else {
+        Some((name, argument))
+    }
+}
+
+/// Parses a clock time written as `H:MM:SS`, optionally with fractions of a second like `0:05:03.2`
+fn parse_clock(input: &str) -> Option<Duration> {
+    let mut components = input.split(':');
+    let hours: u64 = components.next()?.parse().ok()?;
+    let minutes: u64 = components.next()?.parse().ok()?;
+    let seconds = components.next()?;
+    if components.next().is_some() || minutes >= 60 {
+        return None;
+    }
+    let (whole_seconds, fraction) = match seconds.split_once('.') {
+        Some((whole_seconds, fraction)) => (whole_seconds, fraction),
+        None => (seconds, ""),
+    };
+    let whole_seconds: u64 = whole_seconds.parse().ok()?;
+    if whole_seconds >= 60 || fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = format!("{:0<3}", fraction).parse::<u64>().ok()?;
+    Some(Duration::from_millis(
+        ((hours * 60 + minutes) * 60 + whole_seconds) * 1000 + millis,
+    ))
+}
+
+/// Writes a clock time as `H:MM:SS`, with fractions of a second only if there are any
+struct ClockDisplay(Duration);
+
+impl fmt::Display for ClockDisplay {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let seconds = self.0.as_secs();
+        write!(
+            fmt,
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )?;
+        let millis = self.0.subsec_millis();
+        if millis > 0 {
+            write!(fmt, ".{}", format!("{:03}", millis).trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argument_directly_after_command_name() {
+        let (commands, text) = CommentCommands::parse("[%clk0:03:21] [%eval#2] Fast");
+        assert_eq!(commands.clock, Some(Duration::from_secs(201)));
+        assert_eq!(commands.eval, Some(Eval::Mate(2)));
+        assert_eq!(text, "Fast");
+        assert_eq!(
+            commands.to_comment(Some(&text)),
+            "[%eval #2] [%clk 0:03:21] Fast"
+        );
+
+        let comment = "[%clk] [%clk0:03:21 0:03:22] [%csl0:03:21]";
+        let (commands, text) = CommentCommands::parse(comment);
+        assert!(commands.is_empty());
+        assert_eq!(text, comment);
+    }
+
+    #[test]
+    fn parse_clock_and_eval() {
+        let (commands, text) = CommentCommands::parse("[%eval 0.24] [%clk 0:05:03] Good move");
+        assert_eq!(commands.eval, Some(Eval::Centipawns(24)));
+        assert_eq!(commands.clock, Some(Duration::from_secs(303)));
+        assert_eq!(text, "Good move");
+
+        let (commands, text) = CommentCommands::parse("[%clk 1:00:00.25]");
+        assert_eq!(commands.clock, Some(Duration::from_millis(3_600_250)));
+        assert_eq!(commands.eval, None);
+        assert_eq!(text, "");
+    }
+
+    #[test]
+    fn unknown_and_invalid_commands_are_kept() {
+        let comment = "  [%csl Ga4] [%clk 0:61:00] [%eval high]\n";
+        let (commands, text) = CommentCommands::parse(comment);
+        assert!(commands.is_empty());
+        assert_eq!(text, comment);
+    }
+
+    #[test]
+    fn commands_are_written_in_canonical_form() {
+        let (commands, text) = CommentCommands::parse("Good move [%clk 0:5:3.50] [%eval #-3]");
+        assert_eq!(
+            commands.to_comment(Some(&text)),
+            "[%eval #-3] [%clk 0:05:03.5] Good move"
+        );
+        assert_eq!(CommentCommands::default().to_comment(Some("text")), "text");
+    }
+
+    #[test]
+    fn parse_and_display_evals() {
+        assert_eq!("-1.5".parse::<Eval>().unwrap(), Eval::Centipawns(-150));
+        assert_eq!("#3".parse::<Eval>().unwrap(), Eval::Mate(3));
+        assert_eq!(Eval::Centipawns(-5).to_string(), "-0.05");
+        assert_eq!(Eval::Centipawns(1234).to_string(), "12.34");
+        assert_eq!(Eval::Mate(-2).to_string(), "#-2");
+        assert!("#".parse::<Eval>().is_err());
+        assert!("NaN".parse::<Eval>().is_err());
+    }
+
+    #[test]
+    fn text_around_commands_is_kept_verbatim() {
+        let (commands, text) = CommentCommands::parse("[%clk 0:01:00] Para one.\n\nPara two.");
+        assert_eq!(commands.clock, Some(Duration::from_secs(60)));
+        assert_eq!(text, "Para one.\n\nPara two.");
+
+        let (_, text) = CommentCommands::parse("Before  [%eval 0.5]\n  after [%clk 0:00:10]");
+        assert_eq!(text, "Before    after");
+
+        let (_, text) = CommentCommands::parse(" [%eval 0.17] [%clk 0:00:30] ");
+        assert_eq!(text, "");
+    }
+
+    #[test]
+    fn evals_in_centipawns() {
+        assert_eq!(Eval::Centipawns(-35).centipawns(), -35);
+        assert_eq!(Eval::Centipawns(50_000).centipawns(), Eval::MATE_CENTIPAWNS);
+        assert_eq!(Eval::Mate(3).centipawns(), Eval::MATE_CENTIPAWNS);
+        assert_eq!(Eval::Mate(-1).centipawns(), -Eval::MATE_CENTIPAWNS);
+    }
+
+    #[test]
+    fn parse_depth_and_pv() {
+        let comment = "[%eval 0.24] [%depth 20] [%pv e4  e5 Nf3] [%csl Ga4] Best line";
+        let (commands, text) = CommentCommands::parse(comment);
+        assert_eq!(commands.eval, Some(Eval::Centipawns(24)));
+        assert_eq!(commands.depth, Some(20));
+        assert_eq!(commands.pv, vec!["e4", "e5", "Nf3"]);
+        assert_eq!(text, "[%csl Ga4] Best line");
+        assert_eq!(
+            commands.to_comment(Some(&text)),
+            "[%eval 0.24] [%depth 20] [%pv e4 e5 Nf3] [%csl Ga4] Best line"
+        );
+
+        let comment = "[%depth -1] [%depth 20 21] [%pv]";
+        let (commands, text) = CommentCommands::parse(comment);
+        assert!(commands.is_empty());
+        assert_eq!(text, comment);
+    }
+}
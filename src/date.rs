@@ -0,0 +1,127 @@
+//! The structured value of the `Date` tag.
+
+use crate::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// The value of a pgn `Date` tag, written as `YYYY.MM.DD`.
+///
+/// Each component may be unknown, which is written with question marks, like `1992.??.??`.
+/// Dates are ordered chronologically, with unknown components before known ones.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PgnDate {
+    pub year: Option<u16>,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+}
+
+impl FromStr for PgnDate {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Error> {
+        let error = |message: &str| {
+            Error::new_parse_error(format!("Invalid date \"{}\": {}", input, message))
+        };
+        let components: Vec<&str> = input.split('.').collect();
+        if components.len() != 3 {
+            return Err(error("expected three components separated by '.'"));
+        }
+        let year = parse_component(components[0], 4).ok_or_else(|| error("invalid year"))?;
+        let month = parse_component(components[1], 2).ok_or_else(|| error("invalid month"))?;
+        let day = parse_component(components[2], 2).ok_or_else(|| error("invalid day"))?;
+
+        if month.is_some_and(|month| !(1..=12).contains(&month)) {
+            return Err(error("month is out of range"));
+        }
+        if day.is_some_and(|day| !(1..=31).contains(&day)) {
+            return Err(error("day is out of range"));
+        }
+        Ok(PgnDate {
+            year,
+            month: month.map(|month| month as u8),
+            day: day.map(|day| day as u8),
+        })
+    }
+}
+
+/// Parses a component with exactly `width` digits, or `width` question marks for an unknown value
+fn parse_component(component: &str, width: usize) -> Option<Option<u16>> {
+    if component.len() != width {
+        None
+    } else if component.chars().all(|c| c == '?') {
+        Some(None)
+    } else if component.chars().all(|c| c.is_ascii_digit()) {
+        component.parse().ok().map(Some)
+    } else {
+        None
+    }
+}
+
+impl fmt::Display for PgnDate {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.year {
+            Some(year) => write!(fmt, "{:04}", year)?,
+            None => write!(fmt, "????")?,
+        }
+        for component in [self.month, self.day] {
+            match component {
+                Some(value) => write!(fmt, ".{:02}", value)?,
+                None => write!(fmt, ".??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display_dates() {
+        let date: PgnDate = "1992.11.04".parse().unwrap();
+        assert_eq!(
+            date,
+            PgnDate {
+                year: Some(1992),
+                month: Some(11),
+                day: Some(4)
+            }
+        );
+        assert_eq!(date.to_string(), "1992.11.04");
+
+        let date: PgnDate = "1992.??.??".parse().unwrap();
+        assert_eq!(date.month, None);
+        assert_eq!(date.day, None);
+        assert_eq!(date.to_string(), "1992.??.??");
+
+        assert_eq!(
+            "????.??.??".parse::<PgnDate>().unwrap().to_string(),
+            "????.??.??"
+        );
+    }
+
+    #[test]
+    fn reject_malformed_dates() {
+        for date in [
+            "1992.11",
+            "92.11.04",
+            "1992.13.04",
+            "1992.11.32",
+            "1992.00.04",
+            "1992.1?.04",
+        ] {
+            assert!(date.parse::<PgnDate>().is_err(), "{} was accepted", date);
+        }
+    }
+
+    #[test]
+    fn unknown_components_sort_first() {
+        let unknown_month: PgnDate = "1992.??.??".parse().unwrap();
+        let known_month: PgnDate = "1992.01.??".parse().unwrap();
+        let next_year: PgnDate = "1993.??.??".parse().unwrap();
+        assert!(unknown_month < known_month);
+        assert!(known_month < next_year);
+    }
+}
@@ -0,0 +1,198 @@
+//! Parsing and writing of [Extended Position Description][1], a FEN-like format with named operations.
+//!
+//! [1]: https://www.chessprogramming.org/Extended_Position_Description
+
+use crate::Error;
+use std::fmt;
+use std::mem;
+
+/// The number of leading whitespace-separated fields that describe the position.
+const POSITION_FIELDS: usize = 4;
+
+/// A single operation of an EPD record, like `bm Nf3;` or `id "WAC.001";`.
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub struct EpdOperation {
+    /// The name of the operation, like `bm` or `id`
+    pub opcode: String,
+    /// The operands, with the quotes removed from string operands
+    pub operands: Vec<String>,
+}
+
+impl EpdOperation {
+    /// Returns true if the operands of the opcode are strings, which are always quoted.
+    fn has_string_operands(&self) -> bool {
+        let opcode = self.opcode.as_bytes();
+        matches!(
+            self.opcode.as_str(),
+            "id" | "eco" | "nic" | "tcgs" | "tcri" | "tcsi"
+        ) || (opcode.len() == 2
+            && (opcode[0] == b'c' || opcode[0] == b'v')
+            && opcode[1].is_ascii_digit())
+    }
+}
+
+impl fmt::Display for EpdOperation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "{}", self.opcode)?;
+        for operand in self.operands.iter() {
+            let needs_quotes = self.has_string_operands()
+                || operand.is_empty()
+                || operand.contains(|c: char| c.is_whitespace() || c == ';');
+            if needs_quotes {
+                write!(fmt, " \"{}\"", operand)?;
+            } else {
+                write!(fmt, " {}", operand)?;
+            }
+        }
+        write!(fmt, ";")
+    }
+}
+
+/// Splits an EPD record into a FEN string and its operations.
+///
+/// The halfmove clock and fullmove number of the FEN are taken from the `hmvc` and `fmvn` operations, if present.
+pub(crate) fn parse_epd(epd: &str) -> Result<(String, Vec<EpdOperation>), Error> {
+    let mut rest = epd.trim();
+    let mut fields = Vec::with_capacity(POSITION_FIELDS);
+    for _ in 0..POSITION_FIELDS {
+        let field_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if field_end == 0 {
+            return Err(Error::new_parse_error(format!(
+                "Epd \"{}\" has fewer than {} position fields",
+                epd, POSITION_FIELDS
+            )));
+        }
+        fields.push(&rest[..field_end]);
+        rest = rest[field_end..].trim_start();
+    }
+
+    let operations = parse_operations(rest)?;
+    let counter = |opcode: &str, default: &'static str| {
+        operations
+            .iter()
+            .find(|operation| operation.opcode == opcode)
+            .and_then(|operation| operation.operands.first())
+            .map_or(default.to_string(), |operand| operand.clone())
+    };
+    let fen = format!(
+        "{} {} {}",
+        fields.join(" "),
+        counter("hmvc", "0"),
+        counter("fmvn", "1")
+    );
+    Ok((fen, operations))
+}
+
+/// Parses a list of semicolon-terminated operations. The final terminator may be omitted.
+fn parse_operations(input: &str) -> Result<Vec<EpdOperation>, Error> {
+    let mut operations = vec![];
+    let mut tokens: Vec<String> = vec![];
+    let mut chars = input.char_indices().peekable();
+
+    let mut finish_operation = |tokens: &mut Vec<String>, at: usize| {
+        if tokens.is_empty() {
+            return Err(Error::new_parse_error(format!(
+                "Empty epd operation at byte {}",
+                at
+            )));
+        }
+        let opcode = tokens.remove(0);
+        operations.push(EpdOperation {
+            opcode,
+            operands: mem::take(tokens),
+        });
+        Ok(())
+    };
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            ';' => finish_operation(&mut tokens, i)?,
+            '"' => {
+                let mut operand = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => operand.push(c),
+                        None => {
+                            return Err(Error::new_parse_error(format!(
+                                "Unterminated string operand at byte {}",
+                                i
+                            )))
+                        }
+                    }
+                }
+                tokens.push(operand);
+            }
+            c if c.is_whitespace() => (),
+            _ => {
+                let mut end = input.len();
+                while let Some(&(j, c)) = chars.peek() {
+                    if c.is_whitespace() || c == ';' || c == '"' {
+                        end = j;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(input[i..end].to_string());
+            }
+        }
+    }
+    if !tokens.is_empty() {
+        finish_operation(&mut tokens, input.len())?;
+    }
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(opcode: &str, operands: &[&str]) -> EpdOperation {
+        EpdOperation {
+            opcode: opcode.to_string(),
+            operands: operands.iter().map(|operand| operand.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn parse_epd_operations() {
+        let (fen, operations) = parse_epd(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+        )
+        .unwrap();
+        assert_eq!(
+            fen,
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+        assert_eq!(
+            operations,
+            vec![operation("bm", &["Qg6"]), operation("id", &["WAC.001"])]
+        );
+    }
+
+    #[test]
+    fn move_counters_are_taken_from_operations() {
+        let (fen, operations) = parse_epd("8/8/8/8/8/8/8/8 b - - hmvc 12; fmvn 40").unwrap();
+        assert_eq!(fen, "8/8/8/8/8/8/8/8 b - - 12 40");
+        assert_eq!(operations.len(), 2);
+    }
+
+    #[test]
+    fn invalid_epds() {
+        assert!(parse_epd("8/8/8/8/8/8/8/8 w -").is_err());
+        assert!(parse_epd("8/8/8/8/8/8/8/8 w - - bm Qg6;;").is_err());
+        assert!(parse_epd("8/8/8/8/8/8/8/8 w - - id \"unterminated;").is_err());
+    }
+
+    #[test]
+    fn display_operations() {
+        assert_eq!(operation("bm", &["Qg6", "Rf7"]).to_string(), "bm Qg6 Rf7;");
+        assert_eq!(operation("id", &["WAC.001"]).to_string(), "id \"WAC.001\";");
+        assert_eq!(operation("c0", &["Best"]).to_string(), "c0 \"Best\";");
+        assert_eq!(
+            operation("pv", &["two words"]).to_string(),
+            "pv \"two words\";"
+        );
+        assert_eq!(operation("noop", &[]).to_string(), "noop;");
+    }
+}
@@ -0,0 +1,239 @@
+//! Notation variants of chess FEN strings, and syntax validation of standard FEN.
+
+use crate::Error;
+use std::error;
+use std::fmt;
+
+/// The notation used for castling rights in a FEN, which differs for Chess960 positions.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum FenVariant {
+    /// Castling rights written as `KQkq`
+    Standard,
+    /// Castling rights written as the files of the castling rooks, like `HAha`
+    Shredder,
+    /// Castling rights written as `KQkq`, or as the rook's file when the outermost rook is not the castling rook
+    XFen,
+}
+
+/// The six fields of a chess FEN, in order.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, PartialOrd, Ord, Hash)]
+pub enum FenField {
+    PiecePlacement,
+    SideToMove,
+    CastlingRights,
+    EnPassant,
+    HalfmoveClock,
+    FullmoveNumber,
+}
+
+impl FenField {
+    const ALL: [FenField; 6] = [
+        FenField::PiecePlacement,
+        FenField::SideToMove,
+        FenField::CastlingRights,
+        FenField::EnPassant,
+        FenField::HalfmoveClock,
+        FenField::FullmoveNumber,
+    ];
+}
+
+impl fmt::Display for FenField {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let name = match self {
+            FenField::PiecePlacement => "piece placement",
+            FenField::SideToMove => "side to move",
+            FenField::CastlingRights => "castling rights",
+            FenField::EnPassant => "en passant square",
+            FenField::HalfmoveClock => "halfmove clock",
+            FenField::FullmoveNumber => "fullmove number",
+        };
+        write!(fmt, "{}", name)
+    }
+}
+
+/// The reason a FEN failed validation, as returned by [`validate_fen`].
+///
+/// [`validate_fen`]: fn.validate_fen.html
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FenError {
+    /// The field that failed validation, or `None` if the FEN does not have six fields
+    pub field: Option<FenField>,
+    pub message: String,
+}
+
+impl FenError {
+    fn new(field: FenField, message: String) -> FenError {
+        FenError {
+            field: Some(field),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.field {
+            Some(field) => write!(fmt, "Invalid {}: {}", field, self.message),
+            None => write!(fmt, "{}", self.message),
+        }
+    }
+}
+
+impl error::Error for FenError {}
+
+impl From<FenError> for Error {
+    fn from(err: FenError) -> Error {
+        Error::new_parse_error(err)
+    }
+}
+
+/// Checks the syntax of a standard chess FEN, reporting which of its six fields is invalid.
+///
+/// This only validates the notation, so a FEN that passes may still describe an illegal position,
+/// for instance one without kings.
+pub fn validate_fen(fen: &str) -> Result<(), FenError> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() != FenField::ALL.len() {
+        return Err(FenError {
+            field: None,
+            message: format!("Expected 6 fields, found {}", fields.len()),
+        });
+    }
+    for (field, value) in FenField::ALL.iter().zip(fields.iter()) {
+        validate_field(*field, value, fields[1])
+            .map_err(|message| FenError::new(*field, message))?;
+    }
+    Ok(())
+}
+
+/// Validates a single field. The side to move is needed to check the en passant square.
+fn validate_field(field: FenField, value: &str, side_to_move: &str) -> Result<(), String> {
+    match field {
+        FenField::PiecePlacement => validate_piece_placement(value),
+        FenField::SideToMove => match value {
+            "w" | "b" => Ok(()),
+            _ => Err(format!("expected 'w' or 'b', found \"{}\"", value)),
+        },
+        FenField::CastlingRights => {
+            if value == "-" {
+                return Ok(());
+            }
+            let mut remaining = "KQkq";
+            for c in value.chars() {
+                match remaining.find(c) {
+                    Some(i) => remaining = &remaining[i + 1..],
+                    None => {
+                        return Err(format!(
+                            "'{}' is repeated, out of order or not a castling right",
+                            c
+                        ))
+                    }
+                }
+            }
+            Ok(())
+        }
+        FenField::EnPassant => {
+            let expected_rank = if side_to_move == "w" { '6' } else { '3' };
+            match value.chars().collect::<Vec<char>>()[..] {
+                ['-'] => Ok(()),
+                [file, rank] if ('a'..='h').contains(&file) && rank == expected_rank => Ok(()),
+                _ => Err(format!(
+                    "expected '-' or a square on rank {}, found \"{}\"",
+                    expected_rank, value
+                )),
+            }
+        }
+        FenField::HalfmoveClock => value
+            .parse::<u32>()
+            .map(|_| ())
+            .map_err(|_| format!("expected a number, found \"{}\"", value)),
+        FenField::FullmoveNumber => match value.parse::<u32>() {
+            Ok(number) if number > 0 => Ok(()),
+            _ => Err(format!("expected a positive number, found \"{}\"", value)),
+        },
+    }
+}
+
+fn validate_piece_placement(value: &str) -> Result<(), String> {
+    let ranks: Vec<&str> = value.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(format!("expected 8 ranks, found {}", ranks.len()));
+    }
+    for (i, rank) in ranks.iter().enumerate() {
+        // Ranks are listed from the 8th to the 1st
+        let rank_number = 8 - i;
+        let mut squares = 0;
+        let mut previous_was_digit = false;
+        for c in rank.chars() {
+            match c {
+                '1'..='8' if !previous_was_digit => {
+                    squares += c.to_digit(10).unwrap();
+                    previous_was_digit = true;
+                }
+                'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => {
+                    squares += 1;
+                    previous_was_digit = false;
+                }
+                _ => return Err(format!("unexpected '{}' on rank {}", c, rank_number)),
+            }
+        }
+        if squares != 8 {
+            return Err(format!(
+                "rank {} has {} squares, expected 8",
+                rank_number, squares
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_fens() {
+        validate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        validate_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w Kq e6 0 2").unwrap();
+        validate_fen("8/8/8/8/8/8/8/8 b - e3 99 150").unwrap();
+    }
+
+    #[test]
+    fn invalid_fields_are_reported() {
+        let cases = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+                FenField::PiecePlacement,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN w KQkq - 0 1",
+                FenField::PiecePlacement,
+            ),
+            (
+                "rnbqkbnr/pppppppp/44/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenField::PiecePlacement,
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1",
+                FenField::PiecePlacement,
+            ),
+            ("8/8/8/8/8/8/8/8 x - - 0 1", FenField::SideToMove),
+            ("8/8/8/8/8/8/8/8 w qK - 0 1", FenField::CastlingRights),
+            ("8/8/8/8/8/8/8/8 w KK - 0 1", FenField::CastlingRights),
+            ("8/8/8/8/8/8/8/8 w - e3 0 1", FenField::EnPassant),
+            ("8/8/8/8/8/8/8/8 w - - -1 1", FenField::HalfmoveClock),
+            ("8/8/8/8/8/8/8/8 w - - 0 0", FenField::FullmoveNumber),
+        ];
+        for (fen, field) in cases.iter() {
+            let err = validate_fen(fen).unwrap_err();
+            assert_eq!(err.field, Some(*field), "{}: {}", fen, err);
+        }
+    }
+
+    #[test]
+    fn wrong_number_of_fields() {
+        let err = validate_fen("8/8/8/8/8/8/8/8 w - -").unwrap_err();
+        assert_eq!(err.field, None);
+        assert_eq!(err.to_string(), "Expected 6 fields, found 4");
+    }
+}
@@ -0,0 +1,1224 @@
+//! A parsed pgn game, and the parser that produces it.
+
+use crate::comment::CommentCommands;
+use crate::parser::{game_result_from_token, parse_game};
+use crate::raw::raw_games;
+use crate::round::Round;
+use crate::tags::Tags;
+use crate::{Error, ErrorKind, PgnPosition, Visitor};
+use board_game_traits::GameResult;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+/// A single game parsed from pgn, with its tags, start position, moves and result.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "P::Move: serde::Serialize",
+        deserialize = "P::Move: serde::Deserialize<'de>"
+    ))
+)]
+pub struct PgnGame<P: PgnPosition> {
+    /// The game's tag pairs, in the order they appeared in the pgn. If a tag is repeated, the last value is kept.
+    /// Any of the position's `REQUIRED_TAGS` that were missing are appended with their default values.
+    pub tags: Tags,
+    /// The position the game starts from. It is serialized as its FEN.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::fen"))]
+    pub start_position: P,
+    /// A comment before the first move of the game
+    pub comment: Option<String>,
+    /// The moves of the main line, with their annotations and variations
+    pub moves: Vec<AnnotatedMove<P>>,
+    /// The result from the game's terminating token, or `None` if the game is unfinished
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::game_result"))]
+    pub result: Option<GameResult>,
+}
+
+/// A move in a game, with its annotations and any variations that were given as alternatives to it.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "P::Move: serde::Serialize",
+        deserialize = "P::Move: serde::Deserialize<'de>"
+    ))
+)]
+pub struct AnnotatedMove<P: PgnPosition> {
+    pub mv: P::Move,
+    /// The comment after the move. Multiple comments after the same move are joined by a space.
+    pub comment: Option<String>,
+    /// The `[%clk]`, `[%eval]`, `[%depth]` and `[%pv]` commands from the comments after the move, which are removed from `comment`
+    pub commands: CommentCommands,
+    /// The move's [Numeric Annotation Glyphs][1], including those written as suffix annotations like `!?`
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Numeric_Annotation_Glyphs
+    pub nags: Vec<u8>,
+    /// Alternatives to this move. Each variation starts from the position before this move.
+    pub variations: Vec<Variation<P>>,
+}
+
+/// A sequence of moves that is an alternative to a move in a game, known as a recursive annotation variation.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "P::Move: serde::Serialize",
+        deserialize = "P::Move: serde::Deserialize<'de>"
+    ))
+)]
+pub struct Variation<P: PgnPosition> {
+    /// A comment before the first move of the variation
+    pub comment: Option<String>,
+    pub moves: Vec<AnnotatedMove<P>>,
+}
+
+/// Whether a comment is written before or after the move it belongs to, as returned by [`PgnGame::comments`].
+///
+/// [`PgnGame::comments`]: struct.PgnGame.html#method.comments
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum CommentPlacement {
+    BeforeMove,
+    AfterMove,
+}
+
+/// What [`PgnGame::set_start_position`] does if the game's moves are illegal from the new start position.
+///
+/// [`PgnGame::set_start_position`]: struct.PgnGame.html#method.set_start_position
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ConflictPolicy {
+    /// Return the `IllegalMove` error, and leave the game unchanged
+    Reject,
+    /// Remove the first illegal move and every move after it
+    TruncateAtFirstIllegal,
+}
+
+/// A coarse classification of the phase of a game, by the amount of material on the board.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum GamePhase {
+    Opening,
+    Middlegame,
+    Endgame,
+}
+
+impl GamePhase {
+    /// Positions with at least this many pieces, including kings and pawns, are in the opening
+    pub const OPENING_MIN_PIECES: usize = 30;
+    /// Positions with at most this many pieces, including kings and pawns, are in the endgame
+    pub const ENDGAME_MAX_PIECES: usize = 12;
+
+    /// Classifies a position by the number of pieces in the piece placement field of its FEN,
+    /// which is the first field, where each piece is a letter.
+    pub fn from_fen(fen: &str) -> GamePhase {
+        let pieces = fen
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| c.is_alphabetic())
+            .count();
+        if pieces <= Self::ENDGAME_MAX_PIECES {
+            GamePhase::Endgame
+        } else if pieces >= Self::OPENING_MIN_PIECES {
+            GamePhase::Opening
+        } else {
+            GamePhase::Middlegame
+        }
+    }
+}
+
+/// An iterator over the positions after each move of a game's main line.
+///
+/// This struct is created by [`PgnGame::replay`].
+///
+/// [`PgnGame::replay`]: struct.PgnGame.html#method.replay
+pub struct Replay<'a, P: PgnPosition> {
+    position: P,
+    moves: std::slice::Iter<'a, AnnotatedMove<P>>,
+    ply: usize,
+}
+
+impl<'a, P: PgnPosition + Clone> Iterator for Replay<'a, P> {
+    type Item = Result<P, Error>;
+
+    fn next(&mut self) -> Option<Result<P, Error>> {
+        let mv = &self.moves.next()?.mv;
+        let mut legal_moves = vec![];
+        self.position.generate_moves(&mut legal_moves);
+        if !legal_moves.contains(mv) && self.position.null_move().as_ref() != Some(mv) {
+            // Stop after the first illegal move
+            self.moves = [].iter();
+            return Some(Err(Error::new(
+                ErrorKind::IllegalMove,
+                format!("Illegal move {:?} at ply {}", mv, self.ply),
+            )));
+        }
+        self.position.do_move(mv.clone());
+        self.ply += 1;
+        Some(Ok(self.position.clone()))
+    }
+}
+
+impl<P: PgnPosition> PgnGame<P> {
+    /// Returns the game's `Round` tag in structured form, or `None` if the game has no such tag.
+    pub fn round(&self) -> Option<Round> {
+        self.tags.get("Round").map(Round::from)
+    }
+
+    /// Returns the game's `Board` tag, which labels the board of a bughouse game, like `A` or `B`.
+    pub fn board(&self) -> Option<&str> {
+        self.tags.get("Board")
+    }
+
+    /// Returns the main line move played from the position at `ply`, or `None` if the game ends before it.
+    ///
+    /// Ply 0 is the start position, so `move_at_ply(0)` is the first move of the game.
+    pub fn move_at_ply(&self, ply: usize) -> Option<&P::Move> {
+        self.moves.get(ply).map(|annotated_move| &annotated_move.mv)
+    }
+
+    /// Returns the comments of the main line in order, with the ply of the move they belong to.
+    ///
+    /// The game's comment comes first, before the move at ply 0, followed by the comment after each move that has one.
+    /// Comments in variations are not included. Pair the plies with [`position_at_ply`] to find the positions they describe.
+    ///
+    /// [`position_at_ply`]: #method.position_at_ply
+    pub fn comments(&self) -> impl Iterator<Item = (usize, CommentPlacement, &str)> + '_ {
+        let game_comment = self
+            .comment
+            .as_deref()
+            .map(|comment| (0, CommentPlacement::BeforeMove, comment));
+        let move_comments = self
+            .moves
+            .iter()
+            .enumerate()
+            .filter_map(|(ply, annotated_move)| {
+                let comment = annotated_move.comment.as_deref()?;
+                Some((ply, CommentPlacement::AfterMove, comment))
+            });
+        game_comment.into_iter().chain(move_comments)
+    }
+
+    /// Returns the main line moves after which the `[%eval]` evaluation changed by more than `threshold_centipawns`,
+    /// with the change in centipawns from the first player's point of view.
+    ///
+    /// Each move's evaluation is compared with the previous move's, using [`Eval::centipawns`], so forced mates count
+    /// as large, bounded values. A pair of consecutive moves is skipped if either of them has no evaluation.
+    ///
+    /// [`Eval::centipawns`]: enum.Eval.html#method.centipawns
+    pub fn eval_swings(&self, threshold_centipawns: i32) -> Vec<(usize, i32)> {
+        self.moves
+            .windows(2)
+            .enumerate()
+            .filter_map(|(ply, pair)| {
+                let previous = pair[0].commands.eval?.centipawns();
+                let change = pair[1].commands.eval?.centipawns() - previous;
+                if change.abs() > threshold_centipawns {
+                    Some((ply + 1, change))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Sets or removes the comment after the main line move at index `ply`.
+    pub fn set_comment(&mut self, ply: usize, comment: Option<String>) -> Result<(), Error> {
+        self.set_comment_at(&[ply], comment)
+    }
+
+    /// Adds a Numeric Annotation Glyph to the main line move at index `ply`.
+    pub fn add_nag(&mut self, ply: usize, nag: u8) -> Result<(), Error> {
+        self.add_nag_at(&[ply], nag)
+    }
+
+    /// Removes all Numeric Annotation Glyphs from the main line move at index `ply`.
+    pub fn clear_nags(&mut self, ply: usize) -> Result<(), Error> {
+        self.clear_nags_at(&[ply])
+    }
+
+    /// Sets or removes the comment after the move at `path`. See [`move_at_path_mut`] for the path format.
+    ///
+    /// [`move_at_path_mut`]: #method.move_at_path_mut
+    pub fn set_comment_at(&mut self, path: &[usize], comment: Option<String>) -> Result<(), Error> {
+        self.move_at_path_mut(path)?.comment = comment;
+        Ok(())
+    }
+
+    /// Adds a Numeric Annotation Glyph to the move at `path`. See [`move_at_path_mut`] for the path format.
+    ///
+    /// [`move_at_path_mut`]: #method.move_at_path_mut
+    pub fn add_nag_at(&mut self, path: &[usize], nag: u8) -> Result<(), Error> {
+        self.move_at_path_mut(path)?.nags.push(nag);
+        Ok(())
+    }
+
+    /// Removes all Numeric Annotation Glyphs from the move at `path`. See [`move_at_path_mut`] for the path format.
+    ///
+    /// [`move_at_path_mut`]: #method.move_at_path_mut
+    pub fn clear_nags_at(&mut self, path: &[usize]) -> Result<(), Error> {
+        self.move_at_path_mut(path)?.nags.clear();
+        Ok(())
+    }
+
+    /// Returns the move at `path`, which may be inside variations.
+    ///
+    /// The path alternates between move and variation indices, starting and ending with a move index.
+    /// For instance, `[3]` is the fourth move of the main line, and `[3, 0, 1]` is the second move of the first variation to that move.
+    pub fn move_at_path_mut(&mut self, path: &[usize]) -> Result<&mut AnnotatedMove<P>, Error> {
+        let out_of_range = || {
+            Error::new(
+                ErrorKind::Other,
+                format!("Move path {:?} is out of range", path),
+            )
+        };
+        let (&move_index, variation_path) = path.split_first().ok_or_else(out_of_range)?;
+        let mut annotated_move = self.moves.get_mut(move_index).ok_or_else(out_of_range)?;
+        for indices in variation_path.chunks(2) {
+            match *indices {
+                [variation_index, move_index] => {
+                    annotated_move = annotated_move
+                        .variations
+                        .get_mut(variation_index)
+                        .and_then(|variation| variation.moves.get_mut(move_index))
+                        .ok_or_else(out_of_range)?;
+                }
+                _ => return Err(out_of_range()),
+            }
+        }
+        Ok(annotated_move)
+    }
+}
+
+impl<P: PgnPosition + Clone> PgnGame<P> {
+    /// Returns an iterator over the positions after each move of the main line, starting from the start position.
+    ///
+    /// Each move is checked for legality. If a move is illegal, the iterator yields an `IllegalMove` error and stops.
+    pub fn replay(&self) -> Replay<'_, P> {
+        Replay {
+            position: self.start_position.clone(),
+            moves: self.moves.iter(),
+            ply: 0,
+        }
+    }
+
+    /// Replays the main line, calling `f` with the position before each move, the move, and the position after it.
+    pub fn replay_with<F>(&self, mut f: F)
+    where
+        F: FnMut(&P, &P::Move, &P),
+    {
+        let _ = self.try_replay_with(|before, mv, after| -> ControlFlow<()> {
+            f(before, mv, after);
+            ControlFlow::Continue(())
+        });
+    }
+
+    /// Replays the main line like [`replay_with`], but stops early if `f` returns `ControlFlow::Break`.
+    ///
+    /// Returns the `Break` value, or `ControlFlow::Continue` if the whole main line was replayed.
+    ///
+    /// [`replay_with`]: #method.replay_with
+    pub fn try_replay_with<B, F>(&self, mut f: F) -> ControlFlow<B>
+    where
+        F: FnMut(&P, &P::Move, &P) -> ControlFlow<B>,
+    {
+        let mut position = self.start_position.clone();
+        for annotated_move in &self.moves {
+            let previous_position = position.clone();
+            position.do_move(annotated_move.mv.clone());
+            f(&previous_position, &annotated_move.mv, &position)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Returns the game's movetext as it is written by [`write_pgn`], without the tags or the result.
+    ///
+    /// The movetext includes the game's comment, Numeric Annotation Glyphs and variations, and is wrapped at 80 columns.
+    ///
+    /// [`write_pgn`]: fn.write_pgn.html
+    pub fn movetext(&self) -> String {
+        crate::writer::movetext(self)
+    }
+
+    /// Returns the main line moves in standard algebraic notation, with null moves written as `--`.
+    ///
+    /// Returns an `IllegalMove` error if one of the moves is illegal in its position.
+    pub fn moves_san(&self) -> Result<Vec<String>, Error> {
+        let mut sans = Vec::with_capacity(self.moves.len());
+        let mut position = self.start_position.clone();
+        for (annotated_move, next_position) in self.moves.iter().zip(self.replay()) {
+            let next_position = next_position?;
+            if position.null_move().as_ref() == Some(&annotated_move.mv) {
+                sans.push("--".to_string());
+            } else {
+                sans.push(position.move_to_san(&annotated_move.mv));
+            }
+            position = next_position;
+        }
+        Ok(sans)
+    }
+
+    /// Returns the position after `ply` moves of the main line, where ply 0 is the start position.
+    ///
+    /// If the game has fewer than `ply` moves, the final position is returned.
+    /// Returns an `IllegalMove` error if one of the moves is illegal in its position.
+    pub fn position_at_ply(&self, ply: usize) -> Result<P, Error> {
+        let mut position = self.start_position.clone();
+        for next_position in self.replay().take(ply) {
+            position = next_position?;
+        }
+        Ok(position)
+    }
+
+    /// Returns the FEN of the position after `ply` moves of the main line, where ply 0 is the start position.
+    ///
+    /// Unlike [`position_at_ply`], this returns an error if the main line is shorter than `ply`.
+    ///
+    /// [`position_at_ply`]: #method.position_at_ply
+    pub fn fen_after_ply(&self, ply: usize) -> Result<String, Error> {
+        Ok(self.position_after(ply)?.to_fen())
+    }
+
+    /// Returns the phase of the game after `ply` moves of the main line, using [`GamePhase::from_fen`].
+    ///
+    /// Returns an error if the main line is shorter than `ply`.
+    ///
+    /// [`GamePhase::from_fen`]: enum.GamePhase.html#method.from_fen
+    pub fn phase_at_ply(&self, ply: usize) -> Result<GamePhase, Error> {
+        Ok(GamePhase::from_fen(&self.position_after(ply)?.to_fen()))
+    }
+
+    /// Returns the position after `depth` moves of the main line, and the move played from it, if any.
+    ///
+    /// When `depth` is the length of an opening book, this is the last book position and the first move out of book.
+    /// Returns an error if the main line is shorter than `depth`.
+    pub fn book_exit(&self, depth: usize) -> Result<(P, Option<&AnnotatedMove<P>>), Error> {
+        Ok((self.position_after(depth)?, self.moves.get(depth)))
+    }
+
+    /// Splits the game into the main line up to `ply`, and a game that starts from the position at `ply` with the remaining moves.
+    ///
+    /// Both games get a copy of the tags, with a `Part` tag of `1` or `2` to mark which half they are.
+    /// The first game is unfinished, with a `Result` tag of `*`. The second game keeps the game's result,
+    /// and gets a `SetUp` tag and a tag with its start position, if the position has a `START_POSITION_TAG_NAME`.
+    /// Returns an error if the main line is shorter than `ply`.
+    pub fn split_at_ply(&self, ply: usize) -> Result<(PgnGame<P>, PgnGame<P>), Error> {
+        let position = self.position_after(ply)?;
+
+        let mut first_tags = self.tags.clone();
+        first_tags.set("Part", "1");
+        first_tags.set("Result", P::result_to_str(None));
+        let first = PgnGame {
+            tags: first_tags,
+            start_position: self.start_position.clone(),
+            comment: self.comment.clone(),
+            moves: self.moves[..ply].to_vec(),
+            result: None,
+        };
+
+        let mut second_tags = self.tags.clone();
+        second_tags.set("Part", "2");
+        if let Some(tag_name) = P::START_POSITION_TAG_NAME {
+            second_tags.set("SetUp", "1");
+            second_tags.set(tag_name, &position.to_fen());
+        }
+        let second = PgnGame {
+            tags: second_tags,
+            start_position: position,
+            comment: None,
+            moves: self.moves[ply..].to_vec(),
+            result: self.result,
+        };
+        Ok((first, second))
+    }
+
+    /// Returns the ply where the main line leaves an opening book line, given as moves in standard algebraic notation.
+    ///
+    /// The book moves are resolved in the game's positions with `move_from_san`, and compared with the game's moves.
+    /// Returns `None` if the game follows the book for as long as both last, so a game that ends inside the book
+    /// and a book that ends before the game are not deviations. Returns an error if a book move is invalid.
+    pub fn book_deviation(&self, book: &[&str]) -> Result<Option<usize>, Error> {
+        let mut position = self.start_position.clone();
+        for (ply, (san, annotated_move)) in book.iter().zip(&self.moves).enumerate() {
+            let book_move = position.move_from_san(san).map_err(|err| {
+                Error::new_caused_by(
+                    err.kind,
+                    format!("Invalid book move {} at ply {}", san, ply),
+                    err,
+                )
+            })?;
+            if book_move != annotated_move.mv {
+                return Ok(Some(ply));
+            }
+            position.do_move(book_move);
+        }
+        Ok(None)
+    }
+
+    /// Checks that the game's `Result` tag agrees with the result of the final position of the main line.
+    ///
+    /// Games can end without a decided final position, for instance by resignation or agreement,
+    /// so any tag value is accepted if the final position's `game_result` is `None`.
+    /// A mismatch returns an error of kind `Other`, with both results in the message.
+    pub fn verify_result(&self) -> Result<(), Error> {
+        let tag_value = self.tags.result().unwrap_or("*");
+        let tag_result = game_result_from_token::<P>(tag_value).ok_or_else(|| {
+            Error::new(
+                ErrorKind::Other,
+                format!("Invalid Result tag \"{}\"", tag_value),
+            )
+        })?;
+        let final_result = self.position_after(self.moves.len())?.game_result();
+        if final_result.is_some() && final_result != tag_result {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "Result tag \"{}\" does not match the final position's result \"{}\"",
+                    tag_value,
+                    P::result_to_str(final_result)
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Changes the position the game starts from, and checks the main line moves against it like [`replay`].
+    ///
+    /// If a move is illegal from the new start position, `on_conflict` decides whether the game is rejected or truncated.
+    /// A truncated game is unfinished, with a result of `None` and a `Result` tag of `*`.
+    /// The `SetUp` tag and the tag with the start position are set, if the position has a `START_POSITION_TAG_NAME`.
+    ///
+    /// [`replay`]: #method.replay
+    pub fn set_start_position(
+        &mut self,
+        position: P,
+        on_conflict: ConflictPolicy,
+    ) -> Result<(), Error> {
+        let mut replay = Replay {
+            position: position.clone(),
+            moves: self.moves.iter(),
+            ply: 0,
+        };
+        if let Some(err) = replay.by_ref().find_map(Result::err) {
+            let first_illegal_ply = replay.ply;
+            match on_conflict {
+                ConflictPolicy::Reject => return Err(err),
+                ConflictPolicy::TruncateAtFirstIllegal => {
+                    self.moves.truncate(first_illegal_ply);
+                    self.result = None;
+                    self.tags.set("Result", P::result_to_str(None));
+                }
+            }
+        }
+        if let Some(tag_name) = P::START_POSITION_TAG_NAME {
+            self.tags.set("SetUp", "1");
+            self.tags.set(tag_name, &position.to_fen());
+        }
+        self.start_position = position;
+        Ok(())
+    }
+
+    /// Sets the game's result from the final position of the main line, if that position has a result.
+    ///
+    /// The conflict policy is:
+    /// * If the final position's `game_result` is `None`, the game is left unchanged. This allows games that ended
+    ///   by resignation, agreement or on time, whatever result they declare.
+    /// * If the final position has a result, and neither the game's `result` nor its `Result` tag declare a different
+    ///   decided result, both are set to the final position's result. An unfinished result (`*`) or an invalid
+    ///   `Result` tag is not a conflict, and is replaced.
+    /// * Otherwise, the declared result conflicts with the final position, and an error of kind `Other` is returned
+    ///   with both results in the message. The game is left unchanged.
+    ///
+    /// Returns an `IllegalMove` error if one of the moves is illegal in its position.
+    pub fn reconcile_result(&mut self) -> Result<(), Error> {
+        let final_result = match self.position_at_ply(self.moves.len())?.game_result() {
+            Some(result) => result,
+            None => return Ok(()),
+        };
+        let tag_result = self
+            .tags
+            .result()
+            .and_then(game_result_from_token::<P>)
+            .flatten();
+        for declared_result in [self.result, tag_result].iter().flatten() {
+            if *declared_result != final_result {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!(
+                        "Declared result \"{}\" conflicts with the final position's result \"{}\"",
+                        P::result_to_str(Some(*declared_result)),
+                        P::result_to_str(Some(final_result))
+                    ),
+                ));
+            }
+        }
+        self.result = Some(final_result);
+        self.tags.set("Result", P::result_to_str(self.result));
+        Ok(())
+    }
+
+    /// Replays the first `ply` moves of the main line
+    fn position_after(&self, ply: usize) -> Result<P, Error> {
+        if ply > self.moves.len() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "Ply {} is out of range for a game with {} moves",
+                    ply,
+                    self.moves.len()
+                ),
+            ));
+        }
+        let mut position = self.start_position.clone();
+        for annotated_move in &self.moves[..ply] {
+            position.do_move(annotated_move.mv.clone());
+        }
+        Ok(position)
+    }
+
+    /// Returns every position that occurs more than once in the main line, with the plies where it occurs.
+    ///
+    /// Ply 0 is the start position. Positions are compared by their `position_key_without_counters`,
+    /// so castling and en passant rights are part of the comparison, as required by repetition rules.
+    /// The positions are returned in the order they first occurred.
+    pub fn repeated_positions(&self) -> Vec<(String, Vec<usize>)> {
+        let mut occurrences: Vec<(String, Vec<usize>)> = vec![];
+        let mut indices: HashMap<String, usize> = HashMap::new();
+        let mut position = self.start_position.clone();
+        for ply in 0..=self.moves.len() {
+            let key = position.position_key_without_counters();
+            match indices.get(&key) {
+                Some(&index) => occurrences[index].1.push(ply),
+                None => {
+                    indices.insert(key.clone(), occurrences.len());
+                    occurrences.push((key, vec![ply]));
+                }
+            }
+            if let Some(mv) = self.moves.get(ply) {
+                position.do_move(mv.mv.clone());
+            }
+        }
+        occurrences.retain(|(_, plies)| plies.len() > 1);
+        occurrences
+    }
+}
+
+impl<P: PgnPosition> Default for Variation<P> {
+    fn default() -> Self {
+        Variation {
+            comment: None,
+            moves: vec![],
+        }
+    }
+}
+
+/// Parses a single game from pgn.
+///
+/// Errors include the byte offset in `input` where parsing failed.
+pub fn parse_pgn<P>(input: &str) -> Result<PgnGame<P>, Error>
+where
+    P: PgnPosition + Clone,
+{
+    let mut games = raw_games(input);
+    let game = games
+        .next()
+        .ok_or_else(|| Error::new_parse_error("No game found in input".to_string()))??;
+    if games.next().is_some() {
+        return Err(Error::new_parse_error(format!(
+            "Expected a single game, found a second game at byte {}",
+            game.text().len()
+        )));
+    }
+
+    build_game(game.text())
+}
+
+/// Parses the text of a single game into a `PgnGame`
+pub(crate) fn build_game<P>(input: &str) -> Result<PgnGame<P>, Error>
+where
+    P: PgnPosition + Clone,
+{
+    let mut builder = GameBuilder::default();
+    parse_game(input, &mut builder)?;
+    builder.finish()
+}
+
+/// Builds a `PgnGame` from the events of a single game.
+struct GameBuilder<P: PgnPosition> {
+    tags: Tags,
+    start_position: Option<P>,
+    /// The main line, followed by the variations currently being parsed
+    lines: Vec<Variation<P>>,
+    result: Option<GameResult>,
+}
+
+impl<P: PgnPosition> Default for GameBuilder<P> {
+    fn default() -> Self {
+        GameBuilder {
+            tags: Tags::new(),
+            start_position: None,
+            lines: vec![Variation::default()],
+            result: None,
+        }
+    }
+}
+
+impl<P: PgnPosition> GameBuilder<P> {
+    fn finish(mut self) -> Result<PgnGame<P>, Error> {
+        let mut tags = self.tags;
+        for (name, default_value) in P::REQUIRED_TAGS {
+            if tags.get(name).is_none() {
+                tags.set(name, default_value);
+            }
+        }
+        let start_position = self
+            .start_position
+            .ok_or_else(|| Error::new_parse_error("Game has no start position".to_string()))?;
+        let main_line = self.lines.swap_remove(0);
+        Ok(PgnGame {
+            tags,
+            start_position,
+            comment: main_line.comment,
+            moves: main_line.moves,
+            result: self.result,
+        })
+    }
+
+    fn current_line(&mut self) -> &mut Variation<P> {
+        self.lines.last_mut().unwrap()
+    }
+}
+
+impl<P: PgnPosition + Clone> Visitor<P> for GameBuilder<P> {
+    fn tag(&mut self, key: &str, value: &str) {
+        self.tags.set(key, value);
+    }
+
+    fn start_position(&mut self, position: &P) {
+        self.start_position = Some(position.clone());
+    }
+
+    fn san_move(&mut self, mv: &P::Move) {
+        self.current_line().moves.push(AnnotatedMove {
+            mv: mv.clone(),
+            comment: None,
+            commands: CommentCommands::default(),
+            nags: vec![],
+            variations: vec![],
+        });
+    }
+
+    fn comment(&mut self, comment: &str) {
+        let line = self.current_line();
+        let (existing_comment, comment) = match line.moves.last_mut() {
+            Some(last_move) => {
+                let (mut commands, text) = CommentCommands::parse(comment);
+                if !commands.pv.is_empty() {
+                    last_move.commands.pv = std::mem::take(&mut commands.pv);
+                }
+                if commands.clock.is_some() {
+                    last_move.commands.clock = commands.clock;
+                }
+                if commands.eval.is_some() {
+                    last_move.commands.eval = commands.eval;
+                }
+                if commands.depth.is_some() {
+                    last_move.commands.depth = commands.depth;
+                }
+                if !commands.is_empty() && text.is_empty() {
+                    return;
+                }
+                (&mut last_move.comment, Cow::Owned(text))
+            }
+            None => (&mut line.comment, Cow::Borrowed(comment)),
+        };
+        match existing_comment {
+            Some(existing_comment) => {
+                existing_comment.push(' ');
+                existing_comment.push_str(&comment);
+            }
+            None => *existing_comment = Some(comment.into_owned()),
+        }
+    }
+
+    fn nag(&mut self, nag: u8) {
+        // Glyphs before the first move of a line have no move to annotate, and are dropped
+        if let Some(last_move) = self.current_line().moves.last_mut() {
+            last_move.nags.push(nag);
+        }
+    }
+
+    fn begin_variation(&mut self) {
+        self.lines.push(Variation::default());
+    }
+
+    fn end_variation(&mut self) {
+        let variation = self.lines.pop().unwrap();
+        // The parser guarantees that a variation follows a move
+        self.current_line()
+            .moves
+            .last_mut()
+            .unwrap()
+            .variations
+            .push(variation);
+    }
+
+    fn end_game(&mut self, result: Option<GameResult>) {
+        self.result = result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{write, MockPosition};
+    use crate::Eval;
+
+    fn parse(input: &str) -> PgnGame<MockPosition> {
+        parse_pgn(input).unwrap()
+    }
+
+    #[test]
+    fn comments_before_and_inside_variations() {
+        let input = "1. e4 e5 2. Nf3 {Good} (2. d4 {Center} (2. Nc3 {Develops}) d5) Nc6 *";
+        let game = parse(input);
+        assert_eq!(game.moves[2].mv, "Nf3");
+        assert_eq!(game.moves[2].comment.as_deref(), Some("Good"));
+        let variation = &game.moves[2].variations[0];
+        assert_eq!(variation.comment, None);
+        assert_eq!(variation.moves[0].mv, "d4");
+        assert_eq!(variation.moves[0].comment.as_deref(), Some("Center"));
+        let nested_variation = &variation.moves[0].variations[0];
+        assert_eq!(nested_variation.moves[0].mv, "Nc3");
+        assert_eq!(
+            nested_variation.moves[0].comment.as_deref(),
+            Some("Develops")
+        );
+        assert_eq!(variation.moves[1].mv, "d5");
+        assert_eq!(game.moves[3].mv, "Nc6");
+
+        // The writer numbers the variations from the position before Nf3
+        assert!(write(&game).ends_with(
+            "\n1. e4 e5 2. Nf3 {Good} (2. d4 {Center} (2. Nc3 {Develops}) 2... d5) 2... Nc6 *\n"
+        ));
+    }
+
+    #[test]
+    fn parse_pgn_expects_a_single_game() {
+        assert!(parse_pgn::<MockPosition>("").is_err());
+        assert!(
+            parse_pgn::<MockPosition>("[Event \"1\"]\n1. e4 *\n[Event \"2\"]\n1. d4 *\n").is_err()
+        );
+        let err = parse_pgn::<MockPosition>("1. e4 *\n\n1. d4 *\n").unwrap_err();
+        assert_eq!(err.span(), Some(9..11));
+        parse_pgn::<MockPosition>("1. e4 * {Trailing comment}\n").unwrap();
+    }
+
+    #[test]
+    fn build_game_with_annotations() {
+        let game = parse(
+            "[Round \"3.1\"]\n\n{Start} 1. e4 {First} {Second} e5 $2 (1... d5 {Scandinavian} $1) 2. Nf3 {[%clk 0:01:00]} *",
+        );
+        assert_eq!(game.tags.get("Event"), Some("?"));
+        assert_eq!(game.round(), Some(Round::Numbered(vec![3, 1])));
+        assert_eq!(game.comment.as_deref(), Some("Start"));
+        assert_eq!(game.moves.len(), 3);
+        assert_eq!(game.moves[0].comment.as_deref(), Some("First Second"));
+        assert_eq!(game.moves[1].nags, vec![2]);
+        let variation = &game.moves[1].variations[0];
+        assert_eq!(variation.moves[0].mv, "d5");
+        assert_eq!(variation.moves[0].comment.as_deref(), Some("Scandinavian"));
+        assert_eq!(variation.moves[0].nags, vec![1]);
+        assert_eq!(game.moves[2].comment, None);
+        assert_eq!(
+            game.moves[2].commands.clock,
+            Some(std::time::Duration::from_secs(60))
+        );
+        assert_eq!(game.result, None);
+    }
+
+    #[test]
+    fn verify_result() {
+        parse("[Result \"1-0\"]\n1. mate 1-0")
+            .verify_result()
+            .unwrap();
+        parse("[Result \"0-1\"]\n1. e4 0-1")
+            .verify_result()
+            .unwrap();
+        let err = parse("[Result \"0-1\"]\n1. mate 0-1")
+            .verify_result()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn replay_positions() {
+        let game = parse("1. e4 e5 2. Nf3 *");
+        let fens: Vec<String> = game
+            .replay()
+            .map(|position| position.unwrap().to_fen())
+            .collect();
+        assert_eq!(fens, vec!["start e4", "start e4 e5", "start e4 e5 Nf3"]);
+        assert_eq!(game.position_at_ply(2).unwrap().to_fen(), "start e4 e5");
+        assert_eq!(
+            game.position_at_ply(10).unwrap().to_fen(),
+            "start e4 e5 Nf3"
+        );
+        assert_eq!(game.move_at_ply(2), Some(&"Nf3"));
+        assert_eq!(game.moves_san().unwrap(), vec!["e4", "e5", "Nf3"]);
+        assert!(game.book_exit(4).is_err());
+        let (position, next_move) = game.book_exit(1).unwrap();
+        assert_eq!(position.to_fen(), "start e4");
+        assert_eq!(
+            next_move.map(|annotated_move| annotated_move.mv),
+            Some("e5")
+        );
+    }
+
+    #[test]
+    fn replay_stops_at_illegal_move() {
+        let mut game = parse("1. mate *");
+        game.moves.push(game.moves[0].clone());
+        let mut positions = game.replay();
+        assert!(positions.next().unwrap().is_ok());
+        assert_eq!(
+            positions.next().unwrap().unwrap_err().kind(),
+            ErrorKind::IllegalMove
+        );
+        assert!(positions.next().is_none());
+    }
+
+    #[test]
+    fn comments_with_their_ply() {
+        let game =
+            parse("{Start} 1. e4 {King pawn} e5 (1... d5 {Scandinavian}) 2. Nf3 {Develops} *");
+        assert_eq!(
+            game.comments().collect::<Vec<_>>(),
+            vec![
+                (0, CommentPlacement::BeforeMove, "Start"),
+                (0, CommentPlacement::AfterMove, "King pawn"),
+                (2, CommentPlacement::AfterMove, "Develops"),
+            ]
+        );
+        assert_eq!(parse("1. e4 *").comments().count(), 0);
+    }
+
+    #[test]
+    fn fen_after_ply() {
+        let game = parse("1. e4 e5 2. Nf3 *");
+        assert_eq!(game.fen_after_ply(0).unwrap(), "start");
+        assert_eq!(game.fen_after_ply(2).unwrap(), "start e4 e5");
+        assert_eq!(game.fen_after_ply(3).unwrap(), "start e4 e5 Nf3");
+        assert_eq!(game.fen_after_ply(4).unwrap_err().kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn split_at_ply() {
+        let game = parse("[Result \"1-0\"]\n\n{Start} 1. e4 e5 2. Nf3 {Develops} 1-0");
+
+        let (first, second) = game.split_at_ply(2).unwrap();
+        assert_eq!(
+            write(&first),
+            "[Event \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n[Part \"1\"]\n\n{Start} 1. e4 e5 *\n"
+        );
+        assert_eq!(
+            write(&second),
+            "[Event \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"1-0\"]\n[Part \"2\"]\n[SetUp \"1\"]\n\
+             [FEN \"start e4 e5\"]\n\n2. Nf3 {Develops} 1-0\n"
+        );
+        assert_eq!(parse(&write(&second)).start_position, second.start_position);
+
+        let (first, second) = game.split_at_ply(0).unwrap();
+        assert!(first.moves.is_empty());
+        assert_eq!(second.moves, game.moves);
+        assert_eq!(second.start_position, game.start_position);
+
+        let (first, second) = game.split_at_ply(3).unwrap();
+        assert_eq!(first.moves, game.moves);
+        assert!(second.moves.is_empty());
+        assert_eq!(second.result, Some(GameResult::WhiteWin));
+
+        assert_eq!(game.split_at_ply(4).unwrap_err().kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn book_deviation() {
+        let game = parse("1. e4 e5 2. Nf3 Nc6 *");
+        assert_eq!(
+            game.book_deviation(&["e4", "e5", "Nf3", "Nf6"]).unwrap(),
+            Some(3)
+        );
+        assert_eq!(game.book_deviation(&["d4"]).unwrap(), Some(0));
+        assert_eq!(game.book_deviation(&["e4", "e5"]).unwrap(), None);
+        assert_eq!(
+            game.book_deviation(&["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"])
+                .unwrap(),
+            None
+        );
+        assert_eq!(game.book_deviation(&[]).unwrap(), None);
+
+        let err = game.book_deviation(&["e4", "c5"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+    }
+
+    #[test]
+    fn reconcile_result() {
+        let mut game = parse("1. e4 e5 2. mate *");
+        game.reconcile_result().unwrap();
+        assert_eq!(game.result, Some(GameResult::WhiteWin));
+        assert_eq!(game.tags.result(), Some("1-0"));
+
+        // A decisive result without a decided final position is a resignation, and is kept
+        let mut game = parse("[Result \"0-1\"]\n1. e4 e5 0-1");
+        game.reconcile_result().unwrap();
+        assert_eq!(game.result, Some(GameResult::BlackWin));
+        assert_eq!(game.tags.result(), Some("0-1"));
+
+        let mut game = parse("[Result \"1/2-1/2\"]\n1. mate 1/2-1/2");
+        let unchanged = game.clone();
+        let err = game.reconcile_result().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(game, unchanged);
+
+        // The Result tag conflicts, even if the terminating token does not
+        let mut game = parse("[Result \"0-1\"]\n1. mate *");
+        assert_eq!(
+            game.reconcile_result().unwrap_err().kind(),
+            ErrorKind::Other
+        );
+        assert_eq!(game.result, None);
+    }
+
+    #[test]
+    fn board_tag_round_trips() {
+        let input = "[Event \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n[Board \"A\"]\n\n1. e4 *\n";
+        let game = parse(input);
+        assert_eq!(game.board(), Some("A"));
+        assert_eq!(write(&game), input);
+        assert_eq!(parse("1. e4 *").board(), None);
+    }
+
+    #[test]
+    fn set_start_position() {
+        let game = parse("[Result \"1-0\"]\n1. e4 -- 2. Nf3 1-0");
+
+        // Null moves are illegal in the new start position
+        let mut rejected = game.clone();
+        let err = rejected
+            .set_start_position(
+                MockPosition::from_fen("nonull").unwrap(),
+                ConflictPolicy::Reject,
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert_eq!(rejected, game);
+
+        let position = MockPosition::from_fen("start d4 d5").unwrap();
+        let mut accepted = game.clone();
+        accepted
+            .set_start_position(position.clone(), ConflictPolicy::Reject)
+            .unwrap();
+        assert_eq!(accepted.start_position, position);
+        assert_eq!(accepted.moves, game.moves);
+        assert_eq!(accepted.tags.get("SetUp"), Some("1"));
+        assert_eq!(accepted.tags.get("FEN"), Some("start d4 d5"));
+        assert_eq!(accepted.result, Some(GameResult::WhiteWin));
+    }
+
+    #[test]
+    fn set_start_position_truncates_at_first_illegal_move() {
+        let mut game = parse("[Result \"1-0\"]\n1. e4 e5 2. Nf3 -- 3. Nc3 1-0");
+        let position = MockPosition::from_fen("nonull").unwrap();
+        game.set_start_position(position.clone(), ConflictPolicy::TruncateAtFirstIllegal)
+            .unwrap();
+        let moves: Vec<_> = game.moves.iter().map(|mv| mv.mv).collect();
+        assert_eq!(moves, vec!["e4", "e5", "Nf3"]);
+        assert_eq!(game.result, None);
+        assert_eq!(game.tags.result(), Some("*"));
+        assert_eq!(game.start_position, position);
+        assert_eq!(game.tags.get("FEN"), Some("nonull"));
+    }
+
+    #[test]
+    fn eval_swings() {
+        let game = parse(
+            "1. e4 {[%eval 0.2]} e5 {[%eval 0.3]} 2. Qh5 {[%eval -1.5]} Nc6 3. Nf3 {[%eval 0.1]} \
+             a6 {[%eval #4]} *",
+        );
+        // The pair around Nc6, which has no evaluation, is skipped
+        assert_eq!(
+            game.eval_swings(100),
+            vec![(2, -180), (5, Eval::MATE_CENTIPAWNS - 10)]
+        );
+        assert_eq!(game.eval_swings(200), vec![(5, Eval::MATE_CENTIPAWNS - 10)]);
+        assert!(game.eval_swings(Eval::MATE_CENTIPAWNS).is_empty());
+    }
+
+    #[test]
+    fn annotate_moves() {
+        let mut game = parse("1. e4 e5 $1 {Good} 2. Nf3 *");
+        game.set_comment(0, Some("Best by test".to_string()))
+            .unwrap();
+        game.set_comment(1, None).unwrap();
+        game.add_nag(2, 3).unwrap();
+        game.add_nag(2, 14).unwrap();
+        game.clear_nags(1).unwrap();
+        assert_eq!(
+            write(&game).lines().last(),
+            Some("1. e4 {Best by test} 1... e5 2. Nf3 $3 $14 *")
+        );
+
+        assert_eq!(
+            game.set_comment(3, None).unwrap_err().kind(),
+            ErrorKind::Other
+        );
+        assert_eq!(game.add_nag(3, 1).unwrap_err().kind(), ErrorKind::Other);
+        assert_eq!(game.clear_nags(3).unwrap_err().kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn moves_at_paths() {
+        let mut game = parse("1. e4 e5 (1... d5 2. Nf3 (2. Nc3 Nf6)) (1... Nc6) 2. Nf3 *");
+        assert_eq!(game.move_at_path_mut(&[1]).unwrap().mv, "e5");
+        assert_eq!(game.move_at_path_mut(&[1, 0, 1]).unwrap().mv, "Nf3");
+        assert_eq!(game.move_at_path_mut(&[1, 1, 0]).unwrap().mv, "Nc6");
+        assert_eq!(game.move_at_path_mut(&[1, 0, 1, 0, 1]).unwrap().mv, "Nf6");
+
+        game.set_comment_at(&[1, 0, 1, 0, 0], Some("Also good".to_string()))
+            .unwrap();
+        game.add_nag_at(&[1, 1, 0], 6).unwrap();
+        assert_eq!(
+            game.moves[1].variations[0].moves[1].variations[0].moves[0]
+                .comment
+                .as_deref(),
+            Some("Also good")
+        );
+        game.clear_nags_at(&[1, 1, 0]).unwrap();
+        assert!(game.moves[1].variations[1].moves[0].nags.is_empty());
+
+        for path in [
+            &[][..],
+            &[3],
+            &[1, 2, 0],
+            &[1, 0, 2],
+            &[0, 0, 0],
+            // Paths of even length end with a variation index instead of a move index
+            &[1, 0],
+            &[1, 0, 1, 0],
+        ]
+        .iter()
+        {
+            let err = game.move_at_path_mut(path).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Other);
+            assert_eq!(
+                err.to_string(),
+                format!("Move path {:?} is out of range", path)
+            );
+        }
+    }
+
+    #[test]
+    fn replay_with_each_move() {
+        let game = parse("1. e4 e5 2. Nf3 *");
+        let mut replayed = vec![];
+        game.replay_with(|before, mv, after| {
+            replayed.push((before.to_fen(), *mv, after.to_fen()));
+        });
+        assert_eq!(
+            replayed,
+            vec![
+                ("start".to_string(), "e4", "start e4".to_string()),
+                ("start e4".to_string(), "e5", "start e4 e5".to_string()),
+                (
+                    "start e4 e5".to_string(),
+                    "Nf3",
+                    "start e4 e5 Nf3".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn try_replay_with_stops_at_break() {
+        let game = parse("1. e4 e5 2. Qh5 Nc6 3. Nf3 *");
+        let mut moves = vec![];
+        let flow = game.try_replay_with(|_, mv, after| {
+            moves.push(*mv);
+            if *mv == "Qh5" {
+                ControlFlow::Break(after.to_fen())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(flow, ControlFlow::Break("start e4 e5 Qh5".to_string()));
+        assert_eq!(moves, vec!["e4", "e5", "Qh5"]);
+
+        let mut count = 0;
+        let flow: ControlFlow<()> = game.try_replay_with(|_, _, _| {
+            count += 1;
+            ControlFlow::Continue(())
+        });
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn repeated_positions() {
+        assert!(parse("1. e4 e5 2. Nf3 *").repeated_positions().is_empty());
+
+        // The mock position's key is the first four fields of its FEN, so every position from ply 3 on has the same key
+        let game = parse("1. e4 e5 2. Nf3 Nc6 3. Bb5 *");
+        assert_eq!(
+            game.repeated_positions(),
+            vec![("start e4 e5 Nf3".to_string(), vec![3, 4, 5])]
+        );
+    }
+
+    #[test]
+    fn game_phase_thresholds() {
+        let fen = |pieces: usize| format!("{}/8/8 w - - 0 1", "p".repeat(pieces));
+        assert_eq!(
+            GamePhase::from_fen(&fen(GamePhase::OPENING_MIN_PIECES)),
+            GamePhase::Opening
+        );
+        assert_eq!(
+            GamePhase::from_fen(&fen(GamePhase::OPENING_MIN_PIECES - 1)),
+            GamePhase::Middlegame
+        );
+        assert_eq!(
+            GamePhase::from_fen(&fen(GamePhase::ENDGAME_MAX_PIECES + 1)),
+            GamePhase::Middlegame
+        );
+        assert_eq!(
+            GamePhase::from_fen(&fen(GamePhase::ENDGAME_MAX_PIECES)),
+            GamePhase::Endgame
+        );
+        assert_eq!(
+            GamePhase::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            GamePhase::Opening
+        );
+        // Only the piece placement field is counted, so the side to move and castling rights are not pieces
+        assert_eq!(
+            GamePhase::from_fen("4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1"),
+            GamePhase::Endgame
+        );
+        assert_eq!(GamePhase::from_fen(""), GamePhase::Endgame);
+    }
+
+    #[test]
+    fn phase_at_ply_out_of_range() {
+        let game = parse("1. e4 e5 *");
+        // The first field of the mock FENs is `start`, which counts as five pieces
+        assert_eq!(game.phase_at_ply(2).unwrap(), GamePhase::Endgame);
+        let err = game.phase_at_ply(3).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(
+            err.to_string(),
+            "Ply 3 is out of range for a game with 2 moves"
+        );
+    }
+}
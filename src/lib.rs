@@ -6,9 +6,47 @@
 
 extern crate board_game_traits;
 
-use board_game_traits::{GameResult, Position};
+mod comment;
+mod date;
+mod epd;
+mod fen;
+mod game;
+#[cfg(test)]
+mod mock;
+mod parser;
+mod raw;
+mod reader;
+mod round;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod tags;
+pub mod test_utils;
+mod writer;
+
+pub use comment::{CommentCommands, Eval};
+pub use date::PgnDate;
+pub use epd::EpdOperation;
+pub use fen::{validate_fen, FenError, FenField, FenVariant};
+pub use game::{
+    parse_pgn, AnnotatedMove, CommentPlacement, ConflictPolicy, GamePhase, PgnGame, Replay,
+    Variation,
+};
+pub use parser::{parse_bytes, parse_stream, Encoding, Visitor};
+pub use raw::{raw_games, RawGame, RawGames, RawTags};
+pub use reader::{FilterTags, PgnReader};
+pub use round::Round;
+pub use tags::Tags;
+pub use writer::{pgn_from_uci, write_pgn};
+
+use board_game_traits::{Color, GameResult, Position};
+use std::borrow::Cow;
 use std::error;
 use std::fmt;
+use std::io;
+use std::iter;
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::str;
 
 /// A list of general categories of errors related to pgn parsing.
 ///
@@ -18,12 +56,14 @@ use std::fmt;
 ///
 /// [`Error`]: struct.Error.html
 #[derive(Clone, Copy, Eq, PartialEq, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ErrorKind {
     ParseError,
     AmbiguousMove,
     IllegalMove,
     IllegalPosition,
     IoError,
+    EncodingError,
     Other,
 }
 
@@ -35,6 +75,9 @@ pub struct Error {
     kind: ErrorKind,
     error: Box<dyn error::Error + Send + Sync>,
     source: Option<Box<dyn error::Error + Send + Sync>>,
+    span: Option<Range<usize>>,
+    game_index: Option<usize>,
+    line: Option<usize>,
 }
 
 impl Error {
@@ -47,6 +90,9 @@ impl Error {
             kind,
             error: error.into(),
             source: None,
+            span: None,
+            game_index: None,
+            line: None,
         }
     }
 
@@ -60,6 +106,9 @@ impl Error {
             kind,
             error: error.into(),
             source: Some(source.into()),
+            span: None,
+            game_index: None,
+            line: None,
         }
     }
 
@@ -72,11 +121,69 @@ impl Error {
             kind: ErrorKind::ParseError,
             error: error.into(),
             source: None,
+            span: None,
+            game_index: None,
+            line: None,
         }
     }
+
+    /// Returns a `ParseError` with an arbitrary payload, for the byte range `span` of the input where parsing failed.
+    pub fn new_parse_error_at<E>(error: E, span: Range<usize>) -> Error
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        Error::new_parse_error(error).with_span(span)
+    }
+
+    /// The general category of the error
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The byte range of the input that caused the error, if known.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// Sets the byte range of the input that caused the error
+    pub(crate) fn with_span(mut self, span: Range<usize>) -> Error {
+        self.span = Some(span);
+        self
+    }
+
+    /// The index of the game that caused the error, starting at 0, for errors from [`PgnReader`], [`parse_stream`] and [`parse_bytes`].
+    ///
+    /// [`PgnReader`]: struct.PgnReader.html
+    /// [`parse_stream`]: fn.parse_stream.html
+    /// [`parse_bytes`]: fn.parse_bytes.html
+    pub fn game_index(&self) -> Option<usize> {
+        self.game_index
+    }
+
+    /// The line of the input that caused the error, starting at 1, for errors from [`PgnReader`], [`parse_stream`] and [`parse_bytes`].
+    ///
+    /// [`PgnReader`]: struct.PgnReader.html
+    /// [`parse_stream`]: fn.parse_stream.html
+    /// [`parse_bytes`]: fn.parse_bytes.html
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Sets the index of the game and the line of the input that caused the error
+    pub(crate) fn with_location(mut self, game_index: usize, line: usize) -> Error {
+        self.game_index = Some(game_index);
+        self.line = Some(line);
+        self
+    }
 }
 
-impl error::Error for Error {}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn error::Error + 'static))
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
@@ -86,6 +193,7 @@ impl fmt::Display for Error {
             ErrorKind::IllegalMove => write!(fmt, "Illegal move. "),
             ErrorKind::IllegalPosition => write!(fmt, "Illegal position. "),
             ErrorKind::IoError => write!(fmt, "IO error. "),
+            ErrorKind::EncodingError => write!(fmt, "Encoding error. "),
             ErrorKind::Other => Ok(()),
         }?;
         write!(fmt, "{}", self.error)?;
@@ -96,6 +204,190 @@ impl fmt::Display for Error {
     }
 }
 
+/// A single token of movetext, as yielded by [`PgnPosition::moves_san_with_numbers`].
+///
+/// [`PgnPosition::moves_san_with_numbers`]: trait.PgnPosition.html#method.moves_san_with_numbers
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Token {
+    /// A move number. The flag is set if the number precedes the second player's move, and is written as `1...`
+    MoveNumber(u32, bool),
+    /// A move in standard algebraic notation
+    Move(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Token::MoveNumber(number, false) => write!(fmt, "{}.", number),
+            Token::MoveNumber(number, true) => write!(fmt, "{}...", number),
+            Token::Move(san) => write!(fmt, "{}", san),
+        }
+    }
+}
+
+/// An iterator over the move numbers and moves of a move sequence.
+///
+/// This struct is created by [`PgnPosition::moves_san_with_numbers`].
+///
+/// [`PgnPosition::moves_san_with_numbers`]: trait.PgnPosition.html#method.moves_san_with_numbers
+pub struct MovetextTokens<'a, P: PgnPosition> {
+    position: P,
+    moves: std::slice::Iter<'a, P::Move>,
+    move_number: u32,
+    pending_move: Option<String>,
+    is_first_move: bool,
+}
+
+impl<'a, P: PgnPosition> Iterator for MovetextTokens<'a, P> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if let Some(san) = self.pending_move.take() {
+            return Some(Token::Move(san));
+        }
+        let mv = self.moves.next()?;
+        let is_null_move = self.position.null_move().as_ref() == Some(mv);
+        let san = if is_null_move {
+            "--".to_string()
+        } else {
+            self.position.move_to_san(mv)
+        };
+        debug_assert!(
+            is_null_move || self.position.move_from_san(&san).ok().as_ref() == Some(mv),
+            "move_to_san returned \"{}\", which move_from_san does not parse back to the same move",
+            san
+        );
+        let side_to_move = self.position.side_to_move();
+        let move_number = self.move_number;
+        let is_first_move = self.is_first_move;
+
+        self.position.do_move(mv.clone());
+        self.is_first_move = false;
+
+        match side_to_move {
+            Color::White => {
+                self.pending_move = Some(san);
+                Some(Token::MoveNumber(move_number, false))
+            }
+            Color::Black => {
+                self.move_number += 1;
+                if is_first_move {
+                    self.pending_move = Some(san);
+                    Some(Token::MoveNumber(move_number, true))
+                } else {
+                    Some(Token::Move(san))
+                }
+            }
+        }
+    }
+}
+
+/// How the origin square of a move is written in [Standard Algebraic Notation][1], to distinguish it from other moves of the same piece type to the same square.
+///
+/// [1]: https://en.wikipedia.org/wiki/Algebraic_notation_(chess)
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Disambiguation {
+    /// The origin square is not written, like `Nd2`
+    None,
+    /// The origin file is written, like `Nbd2`
+    File,
+    /// The origin rank is written, like `N1d2`
+    Rank,
+    /// The full origin square is written, like `Nb1d2`
+    Square,
+}
+
+/// How strictly to parse moves in [`PgnPosition::move_from_san_with`].
+///
+/// [`PgnPosition::move_from_san_with`]: trait.PgnPosition.html#method.move_from_san_with
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SanParseMode {
+    /// Reject moves with missing or incorrect check suffixes
+    Strict,
+    /// Ignore check suffixes, missing or superfluous capture markers, and the case of piece letters
+    Lenient,
+}
+
+/// An iterator over the positions in a newline-separated list of FENs.
+///
+/// This struct is created by [`PgnPosition::from_fens`].
+///
+/// [`PgnPosition::from_fens`]: trait.PgnPosition.html#method.from_fens
+pub struct Fens<'a, P> {
+    lines: iter::Enumerate<str::Lines<'a>>,
+    phantom: PhantomData<fn() -> P>,
+}
+
+impl<'a, P: PgnPosition> Iterator for Fens<'a, P> {
+    type Item = Result<P, Error>;
+
+    fn next(&mut self) -> Option<Result<P, Error>> {
+        loop {
+            let (line_index, line) = self.lines.next()?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            return Some(P::from_fen(line).map_err(|err| {
+                Error::new_caused_by(
+                    err.kind,
+                    format!("Invalid fen \"{}\" on line {}", line, line_index + 1),
+                    err,
+                )
+            }));
+        }
+    }
+}
+
+/// Reads a newline-separated list of FENs, transforms each position with `f`, and writes the resulting FENs.
+///
+/// Each output line corresponds to the input line at the same line number. Empty lines and lines starting with `#` are copied unchanged.
+/// Stops at the first line that fails to parse or transform, returning an error that includes the line number.
+pub fn transform_fens<P, R, W, F>(reader: R, writer: &mut W, mut f: F) -> Result<(), Error>
+where
+    P: PgnPosition,
+    R: io::BufRead,
+    W: io::Write,
+    F: FnMut(P) -> Result<P, Error>,
+{
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| {
+            Error::new_caused_by(
+                ErrorKind::IoError,
+                format!("Failed to read fen on line {}", line_index + 1),
+                err,
+            )
+        })?;
+        let trimmed = line.trim();
+        let output = if trimmed.is_empty() || trimmed.starts_with('#') {
+            line
+        } else {
+            P::from_fen(trimmed)
+                .and_then(&mut f)
+                .map_err(|err| {
+                    Error::new_caused_by(
+                        err.kind,
+                        format!(
+                            "Failed to transform fen \"{}\" on line {}",
+                            trimmed,
+                            line_index + 1
+                        ),
+                        err,
+                    )
+                })?
+                .to_fen()
+        };
+        writeln!(writer, "{}", output).map_err(|err| {
+            Error::new_caused_by(
+                ErrorKind::IoError,
+                format!("Failed to write fen for line {}", line_index + 1),
+                err,
+            )
+        })?;
+    }
+    Ok(())
+}
+
 /// Trait for text representations of game positions and moves.
 ///
 /// The terminology used in this trait is specific to chess and chess variants, but it can be implemented for any game.
@@ -110,6 +402,15 @@ pub trait PgnPosition: Sized + Position + PartialEq {
     const START_POSITION_TAG_NAME: Option<&'static str>;
 
     /// Each possible game result in the pgn
+    ///
+    /// Games with other result tokens, for instance for specific kinds of wins, can override this.
+    /// All parsing and writing of results goes through this list, using [`parse_result`] and [`result_to_str`].
+    /// Several tokens may have the same result, in which case the first one is used when writing a result.
+    /// When parsing, a token that is not in the list is looked up again after [`normalize_result_token`].
+    ///
+    /// [`parse_result`]: #method.parse_result
+    /// [`result_to_str`]: #method.result_to_str
+    /// [`normalize_result_token`]: fn.normalize_result_token.html
     const POSSIBLE_GAME_RESULTS: &'static [(&'static str, Option<GameResult>)] = &[
         ("*", None),
         ("1-0", Some(GameResult::WhiteWin)),
@@ -117,15 +418,37 @@ pub trait PgnPosition: Sized + Position + PartialEq {
         ("1/2-1/2", Some(GameResult::Draw)),
     ];
 
+    /// Returns the game result for a result token from `POSSIBLE_GAME_RESULTS`, or `None` if the token is not a result.
+    ///
+    /// The unknown result `*` is returned as `Some(None)`.
+    #[inline]
+    fn parse_result(token: &str) -> Option<Option<GameResult>> {
+        Self::POSSIBLE_GAME_RESULTS
+            .iter()
+            .find(|(result_string, _)| *result_string == token)
+            .map(|(_, result)| *result)
+    }
+
+    /// Returns the token for a game result from `POSSIBLE_GAME_RESULTS`, or `*` if the result is not listed there.
+    #[inline]
+    fn result_to_str(result: Option<GameResult>) -> &'static str {
+        Self::POSSIBLE_GAME_RESULTS
+            .iter()
+            .find(|(_, possible_result)| *possible_result == result)
+            .map_or("*", |(result_string, _)| result_string)
+    }
+
     /// Returns a more detailed game result string, for games that use these.
     /// Must correspond with `POSSIBLE_GAME_RESULTS`
+    ///
+    /// Returns `None` if the game is not over, or if its result is not listed in `POSSIBLE_GAME_RESULTS`.
     #[inline]
     fn pgn_game_result(&self) -> Option<&'static str> {
-        self.game_result().map(|game_result| match game_result {
-            GameResult::WhiteWin => "1-0",
-            GameResult::BlackWin => "0-1",
-            GameResult::Draw => "1/2-1/2",
-        })
+        let game_result = self.game_result()?;
+        Self::POSSIBLE_GAME_RESULTS
+            .iter()
+            .find(|(_, possible_result)| *possible_result == Some(game_result))
+            .map(|(result_string, _)| *result_string)
     }
 
     /// Each possible move annotation that can appear at the end of a move
@@ -133,6 +456,19 @@ pub trait PgnPosition: Sized + Position + PartialEq {
     /// If one annotation is a substring of another, the longer one must be written first
     const POSSIBLE_MOVE_ANNOTATIONS: &'static [&'static str] = &["!!", "!?", "?!", "??", "!", "?"];
 
+    /// The suffix appended to a move in standard algebraic notation when it gives check
+    /// Implementations of `move_to_san` should use it, and `move_from_san` should accept it
+    const CHECK_SUFFIX: &'static str = "+";
+
+    /// The suffix appended to a move in standard algebraic notation when it gives checkmate
+    /// Implementations of `move_to_san` should use it, and `move_from_san` should accept it
+    const CHECKMATE_SUFFIX: &'static str = "#";
+
+    /// Whether the game supports Chess960 start positions, where castling rights may be written with file letters.
+    ///
+    /// Implementations that set this should also override `from_fen_variant`.
+    const SUPPORTS_CHESS960: bool = false;
+
     /// Constructs a position from [Forsyth–Edwards Notation][1].
     ///
     /// Extensions to this notation exist for all large chess variants
@@ -143,6 +479,58 @@ pub trait PgnPosition: Sized + Position + PartialEq {
         Self::from_fen_with_settings(fen, &Self::Settings::default())
     }
 
+    /// Constructs a position from [Forsyth–Edwards Notation][1], with castling rights written in the given notation.
+    ///
+    /// Defaults to `from_fen` for `FenVariant::Standard`, and returns a `ParseError` for the other variants.
+    /// Implementations that set `SUPPORTS_CHESS960` should override this to parse Shredder-FEN and X-FEN castling rights.
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Forsyth%E2%80%93Edwards_Notation
+    fn from_fen_variant(fen: &str, variant: FenVariant) -> Result<Self, Error> {
+        match variant {
+            FenVariant::Standard => Self::from_fen(fen),
+            _ => Err(Error::new_parse_error(format!(
+                "{:?} castling notation is not supported, in fen \"{}\"",
+                variant, fen
+            ))),
+        }
+    }
+
+    /// Constructs positions from a newline-separated list of [Forsyth–Edwards Notation][1] strings.
+    ///
+    /// Empty lines and lines starting with `#` are skipped. Errors are reported per line, and include the line number.
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Forsyth%E2%80%93Edwards_Notation
+    #[inline]
+    fn from_fens(input: &str) -> Fens<'_, Self> {
+        Fens {
+            lines: input.lines().enumerate(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Constructs a position from each [Forsyth–Edwards Notation][1] string.
+    ///
+    /// The results are returned in the same order as the input, and errors include the index of the failing FEN.
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Forsyth%E2%80%93Edwards_Notation
+    fn batch_from_fens<'a, I>(fens: I) -> Vec<Result<Self, Error>>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        fens.into_iter()
+            .enumerate()
+            .map(|(index, fen)| {
+                Self::from_fen(fen).map_err(|err| {
+                    Error::new_caused_by(
+                        err.kind,
+                        format!("Invalid fen \"{}\" at index {}", fen, index),
+                        err,
+                    )
+                })
+            })
+            .collect()
+    }
+
     /// The number of full moves in the position
     /// It starts at 1 and is incremented after the second player's move
     #[inline]
@@ -164,6 +552,67 @@ pub trait PgnPosition: Sized + Position + PartialEq {
     /// [1]: https://en.wikipedia.org/wiki/Forsyth%E2%80%93Edwards_Notation
     fn to_fen(&self) -> String;
 
+    /// Returns a string that uniquely identifies the position, for use as a key in hash maps and transposition tables.
+    ///
+    /// Defaults to the position's FEN.
+    #[inline]
+    fn position_key(&self) -> String {
+        self.to_fen()
+    }
+
+    /// Returns a string that identifies the position regardless of its move counters, for detecting transpositions and repetitions.
+    ///
+    /// Defaults to the first four fields of the position's FEN, which excludes the halfmove clock and fullmove number in chess.
+    /// Games whose FEN has a different layout should override this.
+    #[inline]
+    fn position_key_without_counters(&self) -> String {
+        self.to_fen()
+            .split_whitespace()
+            .take(4)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Constructs a position from an [Extended Position Description][1], returning the position and its operations.
+    ///
+    /// Defaults to parsing the first four fields as a FEN without move counters, as in chess.
+    /// The counters are taken from the `hmvc` and `fmvn` operations if present, or are otherwise set to 0 and 1.
+    /// Games whose FEN has a different layout should override this.
+    ///
+    /// [1]: https://www.chessprogramming.org/Extended_Position_Description
+    fn from_epd(epd: &str) -> Result<(Self, Vec<EpdOperation>), Error> {
+        let (fen, operations) = epd::parse_epd(epd)?;
+        let position = Self::from_fen(&fen).map_err(|err| {
+            Error::new_caused_by(err.kind, format!("Invalid epd \"{}\"", epd), err)
+        })?;
+        Ok((position, operations))
+    }
+
+    /// Returns an [Extended Position Description][1] of the position with the given operations.
+    ///
+    /// Defaults to [`position_key_without_counters`] followed by the operations.
+    ///
+    /// [1]: https://www.chessprogramming.org/Extended_Position_Description
+    /// [`position_key_without_counters`]: #method.position_key_without_counters
+    fn to_epd(&self, operations: &[EpdOperation]) -> String {
+        let mut epd = self.position_key_without_counters();
+        for operation in operations {
+            epd.push(' ');
+            epd.push_str(&operation.to_string());
+        }
+        epd
+    }
+
+    /// Returns the null move, which passes the turn to the other side, if the game supports it.
+    ///
+    /// Null moves are written as `--` in movetext, and `Z0` is also accepted when parsing.
+    /// These tokens are recognized by the pgn parser and `move_from_san_with`, before `move_from_san` is called.
+    /// Defaults to `None`, in which case null move tokens are `IllegalMove` errors.
+    #[inline]
+    fn null_move(&self) -> Option<Self::Move> {
+        None
+    }
+
     /// Construct a game move from [Standard Algebraic Notation][1], specifically the format used in [pgn notation][2].
     ///
     /// Extensions to this notation exist for all large chess variants.
@@ -172,6 +621,97 @@ pub trait PgnPosition: Sized + Position + PartialEq {
     /// [2]: https://en.wikipedia.org/wiki/Portable_Game_Notation
     fn move_from_san(&self, input: &str) -> Result<Self::Move, Error>;
 
+    /// Returns whether the input is a legal move in [Standard Algebraic Notation][1] in this position.
+    ///
+    /// Defaults to parsing the move with `move_from_san` and checking it with `move_is_legal`,
+    /// because `move_from_san` is not required to check legality.
+    /// Implementations may override this with a cheaper check.
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Algebraic_notation_(chess)
+    fn is_legal_san(&self, input: &str) -> bool {
+        self.move_from_san(input)
+            .is_ok_and(|mv| self.move_is_legal(mv))
+    }
+
+    /// Returns every legal move in the position in [Standard Algebraic Notation][1], sorted alphabetically.
+    ///
+    /// The sorting makes the order independent of the order of move generation.
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Algebraic_notation_(chess)
+    fn legal_sans(&self) -> Vec<String> {
+        let mut legal_moves = vec![];
+        self.generate_moves(&mut legal_moves);
+        let mut sans: Vec<String> = legal_moves.iter().map(|mv| self.move_to_san(mv)).collect();
+        sans.sort();
+        sans
+    }
+
+    /// Construct a game move from [Standard Algebraic Notation][1], with the given strictness.
+    ///
+    /// In strict mode, a check or checkmate suffix must be present exactly when the move gives check or checkmate.
+    /// In lenient mode, check suffixes are ignored, and if the move still cannot be parsed,
+    /// it is matched against the legal moves while ignoring capture markers and the case of the piece letter.
+    /// In both modes, null move tokens are parsed as described in [`null_move`].
+    ///
+    /// [`null_move`]: #method.null_move
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Algebraic_notation_(chess)
+    fn move_from_san_with(&self, input: &str, mode: SanParseMode) -> Result<Self::Move, Error> {
+        let (move_text, annotations) = split_annotations::<Self>(input);
+        let (move_text, check_suffix) = split_check_suffix::<Self>(move_text);
+        if let Some(null_move) = null_move_from_san(self, move_text) {
+            return null_move;
+        }
+        match mode {
+            SanParseMode::Strict => {
+                let mv = self.move_from_san(input)?;
+                let canonical_san = self.move_to_san(&mv);
+                let (_, canonical_check_suffix) = split_check_suffix::<Self>(&canonical_san);
+                if check_suffix == canonical_check_suffix {
+                    Ok(mv)
+                } else {
+                    Err(Error::new_parse_error(format!(
+                        "Move \"{}\" has the wrong check suffix, expected \"{}\"",
+                        input, canonical_san
+                    )))
+                }
+            }
+            SanParseMode::Lenient => {
+                if let Ok(mv) = self.move_from_san(&format!("{}{}", move_text, annotations)) {
+                    return Ok(mv);
+                }
+                let loose_san = |san: &str| -> String {
+                    let (san, _) = split_annotations::<Self>(san);
+                    let (san, _) = split_check_suffix::<Self>(san);
+                    let mut chars = san.chars().filter(|c| *c != 'x' && *c != ':');
+                    chars
+                        .next()
+                        .map(|c| c.to_ascii_uppercase())
+                        .into_iter()
+                        .chain(chars)
+                        .collect()
+                };
+                let input_loose_san = loose_san(move_text);
+                let mut legal_moves = vec![];
+                self.generate_moves(&mut legal_moves);
+                let mut matching_moves = legal_moves
+                    .into_iter()
+                    .filter(|mv| loose_san(&self.move_to_san(mv)) == input_loose_san);
+                match (matching_moves.next(), matching_moves.next()) {
+                    (Some(mv), None) => Ok(mv),
+                    (Some(_), Some(_)) => Err(Error::new(
+                        ErrorKind::AmbiguousMove,
+                        format!("Move \"{}\" matches several legal moves", input),
+                    )),
+                    (None, _) => Err(Error::new(
+                        ErrorKind::IllegalMove,
+                        format!("Move \"{}\" does not match any legal move", input),
+                    )),
+                }
+            }
+        }
+    }
+
     /// Returns a string representation of the move in [Standard Algebraic Notation][1], specifically the format used in [pgn notation][2].
     ///
     /// Extensions to this notation exist for all large chess variants.
@@ -180,11 +720,46 @@ pub trait PgnPosition: Sized + Position + PartialEq {
     /// [2]: https://en.wikipedia.org/wiki/Portable_Game_Notation
     fn move_to_san(&self, mv: &Self::Move) -> String;
 
+    /// Returns how the move's origin square is disambiguated in its [Standard Algebraic Notation][1], for instance `Nbd2` is disambiguated by file.
+    ///
+    /// The default implementation inspects the output of `move_to_san`, assuming chess-style notation where pieces are written with an upper case letter.
+    /// Pawn moves and castling are never considered disambiguated, because the origin file of a pawn capture is always written.
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Algebraic_notation_(chess)
+    fn san_disambiguation(&self, mv: &Self::Move) -> Disambiguation {
+        let san = self.move_to_san(mv);
+        let (san, _) = split_annotations::<Self>(&san);
+        let (san, _) = split_check_suffix::<Self>(san);
+        let san = san.split('=').next().unwrap_or_default();
+        let mut chars: Vec<char> = san.chars().filter(|c| *c != 'x' && *c != '-').collect();
+
+        match chars.first() {
+            Some(c) if c.is_ascii_uppercase() && *c != 'O' => (),
+            _ => return Disambiguation::None,
+        }
+        // Remove a promotion piece written without `=`
+        if chars.len() > 3 && chars[chars.len() - 1].is_ascii_uppercase() {
+            chars.pop();
+        }
+        if chars.len() < 3 {
+            return Disambiguation::None;
+        }
+        match &chars[1..chars.len() - 2] {
+            [file] if file.is_alphabetic() => Disambiguation::File,
+            [rank] if rank.is_ascii_digit() => Disambiguation::Rank,
+            [_, _] => Disambiguation::Square,
+            _ => Disambiguation::None,
+        }
+    }
+
     /// Construct a move from an alternative, [long algebraic notation][1].
     ///
     /// This is mostly used for chess and chess variations in the uci interface, or for convenient debugging.
     /// Implementations may simply wrap this function around `move_from_san` where appropriate.
+    /// In chess, castling is written as the king's two-square move, like `e1g1`, or as the king capturing its own rook in Chess960.
+    /// [`test_utils::assert_castling_from_king_moves`] checks this for an implementation.
     ///
+    /// [`test_utils::assert_castling_from_king_moves`]: test_utils/fn.assert_castling_from_king_moves.html
     /// [1]: https://en.wikipedia.org/wiki/Algebraic_notation_(chess)#Long_algebraic_notation
     fn move_from_lan(&self, input: &str) -> Result<Self::Move, Error>;
 
@@ -195,4 +770,620 @@ pub trait PgnPosition: Sized + Position + PartialEq {
     ///
     /// [1]: https://en.wikipedia.org/wiki/Algebraic_notation_(chess)#Long_algebraic_notation
     fn move_to_lan(&self, mv: &Self::Move) -> String;
+
+    /// Construct a move from the coordinate notation of the [Universal Chess Interface][1], like `e2e4` or `e7e8q`.
+    ///
+    /// Defaults to `move_from_lan`. Implementations whose long algebraic notation differs from the uci format,
+    /// for instance by including piece letters or hyphens, should override this.
+    ///
+    /// [1]: https://www.chessprogramming.org/UCI
+    #[inline]
+    fn move_from_uci(&self, input: &str) -> Result<Self::Move, Error> {
+        self.move_from_lan(input)
+    }
+
+    /// Returns a string representation of the move in the coordinate notation of the [Universal Chess Interface][1].
+    ///
+    /// Defaults to `move_to_lan`. Implementations whose long algebraic notation differs from the uci format should override this.
+    ///
+    /// [1]: https://www.chessprogramming.org/UCI
+    #[inline]
+    fn move_to_uci(&self, mv: &Self::Move) -> String {
+        self.move_to_lan(mv)
+    }
+
+    /// Returns the move in standard algebraic notation, prefixed by its move number, like `1. e4` or `1... e5`.
+    ///
+    /// The second player's moves are always numbered, as is needed when movetext resumes after a comment or variation.
+    /// To write consecutive moves, use [`moves_san_with_numbers`] instead.
+    ///
+    /// [`moves_san_with_numbers`]: #method.moves_san_with_numbers
+    fn move_to_san_numbered(&self, mv: &Self::Move, move_number: usize) -> String {
+        let number = match self.side_to_move() {
+            Color::White => format!("{}.", move_number),
+            Color::Black => format!("{}...", move_number),
+        };
+        format!("{} {}", number, self.move_to_san(mv))
+    }
+
+    /// Returns an iterator over the moves in standard algebraic notation, interleaved with their move numbers, starting from this position.
+    ///
+    /// Numbering starts at `full_move_number`, or 1 if the position does not keep track of it.
+    /// If the second player is to move in this position, the first token is a move number written as `1...`.
+    ///
+    /// The moves must be legal when played in order from this position.
+    /// In debug builds, this panics if `move_to_san` returns a string that `move_from_san` does not parse back to the same move.
+    fn moves_san_with_numbers<'a>(&self, moves: &'a [Self::Move]) -> MovetextTokens<'a, Self>
+    where
+        Self: Clone,
+    {
+        MovetextTokens {
+            position: self.clone(),
+            moves: moves.iter(),
+            move_number: self.full_move_number().unwrap_or(1),
+            pending_move: None,
+            is_first_move: true,
+        }
+    }
+}
+
+/// Normalizes alternative spellings of a game result token to their canonical form, for instance `½-½` to `1/2-1/2`.
+///
+/// En and em dashes, which often replace hyphens in text copied from documents, are also replaced by hyphens.
+/// Tokens that are already canonical are returned unchanged.
+pub fn normalize_result_token(token: &str) -> Cow<'_, str> {
+    if token.contains(['½', '–', '—']) {
+        Cow::Owned(token.replace('½', "1/2").replace(['–', '—'], "-"))
+    } else {
+        Cow::Borrowed(token)
+    }
+}
+
+/// Returns the move suffix annotation, like `!?`, that is equivalent to a [Numeric Annotation Glyph][1], if there is one.
+///
+/// Only the glyphs `$1` to `$6` have suffix annotations.
+///
+/// [1]: https://en.wikipedia.org/wiki/Numeric_Annotation_Glyphs
+pub fn nag_to_annotation(nag: u8) -> Option<&'static str> {
+    match nag {
+        1 => Some("!"),
+        2 => Some("?"),
+        3 => Some("!!"),
+        4 => Some("??"),
+        5 => Some("!?"),
+        6 => Some("?!"),
+        _ => None,
+    }
+}
+
+/// Returns the [Numeric Annotation Glyph][1] that is equivalent to a move suffix annotation, like `!?`, if there is one.
+///
+/// This is the inverse of [`nag_to_annotation`].
+///
+/// [1]: https://en.wikipedia.org/wiki/Numeric_Annotation_Glyphs
+/// [`nag_to_annotation`]: fn.nag_to_annotation.html
+pub fn annotation_to_nag(annotation: &str) -> Option<u8> {
+    (1..=6).find(|&nag| nag_to_annotation(nag) == Some(annotation))
+}
+
+/// Checks that the `Result` tag of a single pgn game agrees with the result token that terminates its movetext.
+///
+/// Only the tag section and the final movetext token are inspected, so the moves are not replayed or validated.
+/// Both results are looked up as the parser does, so alternative spellings like `½-½` and `1/2-1/2` agree.
+pub fn check_result_token_matches_tag<P: PgnPosition>(input: &str) -> Result<(), Error> {
+    let mut tag_result = None;
+    let mut offset = parser::skip_whitespace(input, 0);
+    while input[offset..].starts_with('[') {
+        let (name, value, end) = parser::parse_tag(input, offset)?;
+        if name == "Result" {
+            tag_result = Some(value);
+        }
+        offset = parser::skip_whitespace(input, end);
+    }
+
+    let tag_result =
+        tag_result.ok_or_else(|| Error::new_parse_error("Missing Result tag".to_string()))?;
+    let movetext_result = last_movetext_token(&input[offset..])
+        .ok_or_else(|| Error::new_parse_error("Missing result token in movetext".to_string()))?;
+
+    let parse_result = |token: &str| {
+        parser::game_result_from_token::<P>(token)
+            .ok_or_else(|| Error::new_parse_error(format!("Invalid game result \"{}\"", token)))
+    };
+    if parse_result(&tag_result)? == parse_result(movetext_result)? {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "Result tag \"{}\" does not match movetext result \"{}\"",
+                tag_result, movetext_result
+            ),
+        ))
+    }
+}
+
+/// Splits the move annotations, like `!?`, from the end of a move
+fn split_annotations<P: PgnPosition>(san: &str) -> (&str, &str) {
+    let mut move_end = san.len();
+    while let Some(annotation) = P::POSSIBLE_MOVE_ANNOTATIONS
+        .iter()
+        .find(|annotation| move_end > annotation.len() && san[..move_end].ends_with(*annotation))
+    {
+        move_end -= annotation.len();
+    }
+    san.split_at(move_end)
+}
+
+/// Splits the check or checkmate suffix from the end of a move without annotations
+fn split_check_suffix<P: PgnPosition>(san: &str) -> (&str, &str) {
+    let mut suffixes = [P::CHECK_SUFFIX, P::CHECKMATE_SUFFIX];
+    // Check the longest suffix first, in case one is a suffix of the other
+    suffixes.sort_by_key(|suffix| std::cmp::Reverse(suffix.len()));
+    match suffixes
+        .iter()
+        .find(|suffix| !suffix.is_empty() && san.len() > suffix.len() && san.ends_with(*suffix))
+    {
+        Some(suffix) => san.split_at(san.len() - suffix.len()),
+        None => (san, ""),
+    }
+}
+
+/// Returns the position's null move if `san` is a null move token, or `None` if it is not.
+pub(crate) fn null_move_from_san<P: PgnPosition>(
+    position: &P,
+    san: &str,
+) -> Option<Result<P::Move, Error>> {
+    if san != "--" && san != "Z0" {
+        return None;
+    }
+    Some(position.null_move().ok_or_else(|| {
+        Error::new(
+            ErrorKind::IllegalMove,
+            format!("Null move \"{}\" is not supported", san),
+        )
+    }))
+}
+
+/// Returns the last whitespace-separated token of some movetext, ignoring comments and escape lines.
+fn last_movetext_token(movetext: &str) -> Option<&str> {
+    let mut last_token = None;
+    let mut token_start = None;
+    let mut chars = movetext.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '%' if i == 0 || movetext[..i].ends_with('\n') => {
+                chars.by_ref().find(|(_, c)| *c == '\n');
+            }
+            '{' | ';' => {
+                if let Some(start) = token_start.take() {
+                    last_token = Some(&movetext[start..i]);
+                }
+                let end = if c == '{' { '}' } else { '\n' };
+                chars.by_ref().find(|(_, c)| *c == end);
+            }
+            c if c.is_whitespace() => {
+                if let Some(start) = token_start.take() {
+                    last_token = Some(&movetext[start..i]);
+                }
+            }
+            _ => {
+                if token_start.is_none() {
+                    token_start = Some(i);
+                }
+            }
+        }
+    }
+    if let Some(start) = token_start {
+        last_token = Some(&movetext[start..]);
+    }
+    last_token
+}
+
+/// Removes comments, variations and annotation glyphs from pgn movetext, leaving only move numbers, moves and the game result.
+///
+/// This is a purely lexical operation, so the moves are not validated. The remaining tokens are separated by single spaces.
+///
+/// Returns a `ParseError` if the movetext has unbalanced parentheses or an unterminated comment.
+pub fn strip_movetext_annotations(movetext: &str) -> Result<String, Error> {
+    let mut tokens: Vec<&str> = vec![];
+    let mut variation_depth = 0;
+    let mut token_start = None;
+    let mut chars = movetext.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let is_token_char = !(c.is_whitespace() || "{};()$".contains(c));
+        if !is_token_char {
+            if let Some(start) = token_start.take() {
+                if variation_depth == 0 {
+                    tokens.push(movetext[start..i].trim_end_matches(['!', '?']));
+                }
+            }
+        }
+        match c {
+            '{' => {
+                chars.by_ref().find(|(_, c)| *c == '}').ok_or_else(|| {
+                    Error::new_parse_error_at(
+                        format!("Unterminated comment at byte {}", i),
+                        i..movetext.len(),
+                    )
+                })?;
+            }
+            ';' => {
+                chars.by_ref().find(|(_, c)| *c == '\n');
+            }
+            '(' => variation_depth += 1,
+            ')' => {
+                if variation_depth == 0 {
+                    return Err(Error::new_parse_error_at(
+                        format!("Unmatched ')' at byte {}", i),
+                        i..i + 1,
+                    ));
+                }
+                variation_depth -= 1;
+            }
+            '$' => while chars.next_if(|(_, c)| c.is_ascii_digit()).is_some() {},
+            _ if is_token_char && token_start.is_none() => token_start = Some(i),
+            _ => (),
+        }
+    }
+    if let Some(start) = token_start {
+        if variation_depth == 0 {
+            tokens.push(movetext[start..].trim_end_matches(['!', '?']));
+        }
+    }
+    if variation_depth > 0 {
+        return Err(Error::new_parse_error(format!(
+            "{} unclosed variation(s) in movetext",
+            variation_depth
+        )));
+    }
+    Ok(tokens
+        .into_iter()
+        .filter(|token| !token.is_empty())
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{write, CustomPosition, MockPosition};
+
+    #[test]
+    fn normalize_result_token_replaces_dashes_and_halves() {
+        assert_eq!(normalize_result_token("1\u{2013}0"), "1-0");
+        assert_eq!(normalize_result_token("0\u{2014}1"), "0-1");
+        assert_eq!(normalize_result_token("½–½"), "1/2-1/2");
+        assert_eq!(normalize_result_token("½-½"), "1/2-1/2");
+        assert!(matches!(
+            normalize_result_token("1-0"),
+            Cow::Borrowed("1-0")
+        ));
+    }
+
+    #[test]
+    fn en_dash_result_token() {
+        let game = parse_pgn::<MockPosition>("1. e4 e5 1\u{2013}0\n").unwrap();
+        assert_eq!(game.result, Some(GameResult::WhiteWin));
+
+        assert!(write(&game).ends_with("\n1. e4 e5 1-0\n"));
+    }
+
+    #[test]
+    fn em_dash_result_token() {
+        let game = parse_pgn::<MockPosition>("1. e4 e5 0\u{2014}1\n").unwrap();
+        assert_eq!(game.result, Some(GameResult::BlackWin));
+
+        let game = parse_pgn::<MockPosition>("1. e4 e5 ½\u{2014}½\n").unwrap();
+        assert_eq!(game.result, Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn custom_result_tokens() {
+        let game =
+            parse_pgn::<CustomPosition>("[Result \"R\u{2013}0\"]\n1. e4 R\u{2013}0\n").unwrap();
+        assert_eq!(game.result, Some(GameResult::WhiteWin));
+        assert_eq!(game.tags.result(), Some("R\u{2013}0"));
+        let game = parse_pgn::<CustomPosition>("1. e4 0\u{2013}R\n").unwrap();
+        assert_eq!(game.result, Some(GameResult::BlackWin));
+
+        // Tokens that are not listed are still normalized
+        let game = parse_pgn::<CustomPosition>("1. e4 0\u{2014}1\n").unwrap();
+        assert_eq!(game.result, Some(GameResult::BlackWin));
+        assert!(parse_pgn::<CustomPosition>("1. e4 1/2-1/2\n").is_err());
+
+        assert_eq!(
+            CustomPosition::result_to_str(Some(GameResult::WhiteWin)),
+            "1-0"
+        );
+        assert_eq!(CustomPosition::result_to_str(Some(GameResult::Draw)), "*");
+    }
+
+    #[test]
+    fn pgn_game_result_of_unlisted_result() {
+        let position = |fen| CustomPosition::from_fen(fen).unwrap();
+        assert_eq!(position("start e4").pgn_game_result(), None);
+        assert_eq!(position("start mate").pgn_game_result(), Some("1-0"));
+        assert_eq!(position("start e4 mate").pgn_game_result(), Some("0-1"));
+        assert_eq!(position("start draw").pgn_game_result(), None);
+        assert_eq!(
+            MockPosition::from_fen("start draw")
+                .unwrap()
+                .pgn_game_result(),
+            Some("1/2-1/2")
+        );
+    }
+
+    #[test]
+    fn result_tag_matches_result_token() {
+        let check = check_result_token_matches_tag::<MockPosition>;
+        check("[Event \"?\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n").unwrap();
+        check("[White \"x\"] [Result \"1/2-1/2\"]\n1. e4 {Drawn} ½-½ {Agreed}\n%escape line\n")
+            .unwrap();
+        check("[Result \"0\u{2013}1\"]\n1. e4 e5 0-1").unwrap();
+
+        let err = check("[Result \"1-0\"]\n1. e4 e5 0-1\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert!(err.to_string().contains("\"1-0\""), "{}", err);
+        assert!(err.to_string().contains("\"0-1\""), "{}", err);
+
+        assert!(check("[Event \"?\"]\n1. e4 1-0").is_err());
+        assert!(check("[Result \"1-0\"]\n").is_err());
+        assert!(check("[Result \"won\"]\n1. e4 1-0").is_err());
+        assert_eq!(
+            check("[Result 1-0]\n1. e4 1-0").unwrap_err().span(),
+            Some(8..9)
+        );
+    }
+
+    #[test]
+    fn split_custom_check_suffixes() {
+        assert_eq!(split_check_suffix::<CustomPosition>("Qh5^"), ("Qh5", "^"));
+        assert_eq!(
+            split_check_suffix::<CustomPosition>("mate^^"),
+            ("mate", "^^")
+        );
+        assert_eq!(split_check_suffix::<CustomPosition>("Qh5+"), ("Qh5+", ""));
+        assert_eq!(split_check_suffix::<CustomPosition>("^"), ("^", ""));
+        assert_eq!(split_check_suffix::<MockPosition>("mate#"), ("mate", "#"));
+    }
+
+    #[test]
+    fn move_from_san_with_custom_check_suffixes() {
+        let position = CustomPosition::start_position();
+        let strict = |san| position.move_from_san_with(san, SanParseMode::Strict);
+        assert_eq!(strict("Qh5^").unwrap(), "Qh5");
+        assert_eq!(strict("Qh5^!").unwrap(), "Qh5");
+        assert_eq!(strict("mate^^").unwrap(), "mate");
+        assert_eq!(strict("e4").unwrap(), "e4");
+        assert!(strict("Qh5").is_err());
+        assert!(strict("Qh5+").is_err());
+        assert!(strict("mate^").is_err());
+        assert!(strict("e4^").is_err());
+
+        let lenient = |san| position.move_from_san_with(san, SanParseMode::Lenient);
+        assert_eq!(lenient("Qh5").unwrap(), "Qh5");
+        assert_eq!(lenient("qh5^").unwrap(), "Qh5");
+        assert_eq!(lenient("mate^").unwrap(), "mate");
+        assert_eq!(lenient("e4^^").unwrap(), "e4");
+    }
+
+    #[test]
+    fn custom_check_suffixes_round_trip() {
+        let input = "1. e4 e5 2. Qh5^ Nc6 3. mate^^ 1-0";
+        let game = parse_pgn::<CustomPosition>(input).unwrap();
+        assert_eq!(game.moves[2].mv, "Qh5");
+        assert_eq!(game.result, Some(GameResult::WhiteWin));
+        assert!(parse_pgn::<CustomPosition>("1. e4 e5 2. Qh5+ *").is_err());
+
+        let output = write(&game);
+        assert!(output.ends_with(&format!("\n{}\n", input)), "{}", output);
+        assert_eq!(parse_pgn::<CustomPosition>(&output).unwrap(), game);
+    }
+
+    #[test]
+    fn strip_movetext_annotations_keeps_moves_and_result() {
+        let movetext =
+            "1. e4 {Best by test} e5!? (1... c5 2. Nf3 {Sicilian}) 2. Nf3 $1 ; rest of line\nNc6 1-0";
+        assert_eq!(
+            strip_movetext_annotations(movetext).unwrap(),
+            "1. e4 e5 2. Nf3 Nc6 1-0"
+        );
+    }
+
+    #[test]
+    fn strip_movetext_annotations_rejects_unbalanced_input() {
+        let err = strip_movetext_annotations("1. e4 e5) 2. Nf3").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+        assert_eq!(err.span(), Some(8..9));
+
+        let err = strip_movetext_annotations("1. e4 {unterminated").unwrap_err();
+        assert_eq!(err.span(), Some(6..19));
+
+        assert!(strip_movetext_annotations("1. e4 (1... e5").is_err());
+    }
+
+    #[test]
+    fn movetext_tokens_from_either_side() {
+        let moves = ["e4", "e5", "Nf3"];
+        let tokens: Vec<Token> = MockPosition::start_position()
+            .moves_san_with_numbers(&moves)
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::MoveNumber(1, false),
+                Token::Move("e4".to_string()),
+                Token::Move("e5".to_string()),
+                Token::MoveNumber(2, false),
+                Token::Move("Nf3".to_string()),
+            ]
+        );
+
+        let position = MockPosition::from_fen("start e4").unwrap();
+        let tokens: Vec<Token> = position
+            .moves_san_with_numbers(&["e5", "Nf3", "Nc6", "Qh5"])
+            .collect();
+        assert_eq!(tokens[0], Token::MoveNumber(1, true));
+        let movetext: Vec<String> = tokens.iter().map(Token::to_string).collect();
+        assert_eq!(movetext.join(" "), "1... e5 2. Nf3 Nc6 3. Qh5+");
+    }
+
+    #[test]
+    fn san_disambiguation_from_written_moves() {
+        // The mock position writes any move as itself, so it can be given arbitrary chess moves
+        let position = MockPosition::start_position();
+        let cases = [
+            ("Nbd2", Disambiguation::File),
+            ("Raxe1+", Disambiguation::File),
+            ("N1d2", Disambiguation::Rank),
+            ("R8e1#!", Disambiguation::Rank),
+            ("Qh4e1", Disambiguation::Square),
+            ("Qh4xe1", Disambiguation::Square),
+            ("Nd2", Disambiguation::None),
+            ("Qxh5", Disambiguation::None),
+            ("exd5", Disambiguation::None),
+            ("e8=Q", Disambiguation::None),
+            ("O-O-O", Disambiguation::None),
+        ];
+        for (mv, disambiguation) in cases.iter() {
+            assert_eq!(position.san_disambiguation(mv), *disambiguation, "{}", mv);
+        }
+        assert_eq!(
+            CustomPosition(position).san_disambiguation(&"Rae1^^"),
+            Disambiguation::File
+        );
+    }
+
+    #[test]
+    fn fens_skip_blank_lines_and_comments() {
+        let input = "start\n\n# A comment\n  start e4 e5  \n\t\nnonull d4\nbogus\nstart Nf3\n";
+        let positions: Vec<Result<MockPosition, Error>> = MockPosition::from_fens(input).collect();
+        assert_eq!(positions.len(), 5);
+        assert_eq!(positions[0].as_ref().unwrap().to_fen(), "start");
+        assert_eq!(positions[1].as_ref().unwrap().to_fen(), "start e4 e5");
+        assert_eq!(positions[2].as_ref().unwrap().to_fen(), "nonull d4");
+        assert_eq!(positions[4].as_ref().unwrap().to_fen(), "start Nf3");
+
+        let err = positions[3].as_ref().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+        assert!(
+            err.to_string().contains("Invalid fen \"bogus\" on line 7"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn transform_fens_copies_comments() {
+        let input = "# Positions\nstart e4\n\n  # Indented comment\nnonull\n";
+        let mut output = vec![];
+        transform_fens(
+            input.as_bytes(),
+            &mut output,
+            |mut position: MockPosition| {
+                position.do_move("e5");
+                Ok(position)
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# Positions\nstart e4 e5\n\n  # Indented comment\nnonull e5\n"
+        );
+    }
+
+    #[test]
+    fn transform_fens_errors_have_their_line() {
+        let mut output = vec![];
+        let err = transform_fens(
+            "start\nbogus\nstart e4\n".as_bytes(),
+            &mut output,
+            |position: MockPosition| Ok(position),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+        assert!(err
+            .to_string()
+            .contains("Failed to transform fen \"bogus\" on line 2"));
+        // Lines before the error have already been written
+        assert_eq!(String::from_utf8(output).unwrap(), "start\n");
+
+        let err = transform_fens(
+            "start\n\nstart e4\n".as_bytes(),
+            &mut vec![],
+            |position: MockPosition| {
+                if position.to_fen() == "start e4" {
+                    Err(Error::new(
+                        ErrorKind::IllegalPosition,
+                        "Unsupported position",
+                    ))
+                } else {
+                    Ok(position)
+                }
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalPosition);
+        assert!(err
+            .to_string()
+            .contains("Failed to transform fen \"start e4\" on line 3"));
+    }
+
+    #[test]
+    fn default_epd_conversion() {
+        let epd = "start e4 e5 Nf3 bm Nc6; hmvc 3; id \"Test 1\";";
+        let (position, operations) = MockPosition::from_epd(epd).unwrap();
+        assert_eq!(position.to_fen(), "start e4 e5 Nf3");
+        assert_eq!(operations.len(), 3);
+        assert_eq!(operations[0].opcode, "bm");
+        assert_eq!(operations[0].operands, vec!["Nc6"]);
+        assert_eq!(operations[2].operands, vec!["Test 1"]);
+        assert_eq!(position.to_epd(&operations), epd);
+
+        // Only the first four fields of the FEN are written
+        let position = MockPosition::from_fen("start e4 e5 Nf3 Nc6").unwrap();
+        assert_eq!(position.to_epd(&[]), "start e4 e5 Nf3");
+
+        let err = MockPosition::from_epd("start e4").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+        let err = MockPosition::from_epd("start e4 e5 Kh1 bm Nc6;").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert!(err.to_string().contains("Invalid epd"), "{}", err);
+    }
+
+    #[test]
+    fn is_legal_san() {
+        let position = MockPosition::start_position();
+        assert!(position.is_legal_san("e4"));
+        assert!(position.is_legal_san("Qh5+"));
+        assert!(position.is_legal_san("Nf3!?"));
+        assert!(!position.is_legal_san("Kh1"));
+        assert!(!position.is_legal_san(""));
+
+        // Moves that parse are still illegal after the game has ended
+        let position = MockPosition::from_fen("start mate").unwrap();
+        assert!(position.move_from_san("e4").is_ok());
+        assert!(!position.is_legal_san("e4"));
+    }
+
+    #[test]
+    fn legal_sans_are_sorted() {
+        assert_eq!(
+            MockPosition::start_position().legal_sans(),
+            vec![
+                "Bb5", "Nc3", "Nc6", "Nf3", "Nf6", "O-O", "O-O-O", "Qh5+", "a6", "d4", "d5",
+                "draw", "e4", "e5", "mate#"
+            ]
+        );
+        assert_eq!(
+            CustomPosition(MockPosition::start_position()).legal_sans()[7],
+            "Qh5^"
+        );
+        assert!(MockPosition::from_fen("start draw")
+            .unwrap()
+            .legal_sans()
+            .is_empty());
+    }
 }
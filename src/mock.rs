@@ -0,0 +1,283 @@
+//! A minimal game for unit tests, where the same fixed set of moves is legal in every position until the game ends.
+
+use crate::{write_pgn, Error, ErrorKind, PgnGame, PgnPosition};
+use board_game_traits::{Color, GameResult, Position};
+
+/// The moves that are legal in every position where the game is not over.
+///
+/// `Qh5` gives check, `mate` wins the game for the player who plays it, and `draw` draws it.
+pub(crate) const LEGAL_MOVES: &[&str] = &[
+    "e4", "e5", "d4", "d5", "Nf3", "Nf6", "Nc3", "Nc6", "Bb5", "a6", "Qh5", "O-O", "O-O-O", "mate",
+    "draw",
+];
+
+/// The null move, in positions that support it
+pub(crate) const NULL_MOVE: &str = "null";
+
+/// A position of the mock game, which is the list of moves played since the start.
+///
+/// Its FEN is `start` followed by the moves, or `nonull` followed by the moves for positions without null moves.
+/// Numeric fields, like the move counters appended by the default `from_epd`, are ignored when parsing.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub(crate) struct MockPosition {
+    supports_null_move: bool,
+    moves: Vec<&'static str>,
+}
+
+impl MockPosition {
+    /// Writes a move with the given check and checkmate suffixes
+    pub(crate) fn write_san(mv: &str, check_suffix: &str, checkmate_suffix: &str) -> String {
+        match mv {
+            "Qh5" => format!("{}{}", mv, check_suffix),
+            "mate" => format!("{}{}", mv, checkmate_suffix),
+            _ => mv.to_string(),
+        }
+    }
+
+    /// Parses a move that may end with annotations and the given check or checkmate suffix
+    pub(crate) fn parse_san(
+        input: &str,
+        check_suffix: &str,
+        checkmate_suffix: &str,
+    ) -> Result<&'static str, Error> {
+        let san = input.trim_end_matches(['!', '?']);
+        let san = san
+            .strip_suffix(checkmate_suffix)
+            .or_else(|| san.strip_suffix(check_suffix))
+            .unwrap_or(san);
+        LEGAL_MOVES
+            .iter()
+            .copied()
+            .find(|mv| *mv == san)
+            .ok_or_else(|| Error::new(ErrorKind::IllegalMove, format!("Unknown move {}", input)))
+    }
+
+    /// Returns a castling move in long algebraic notation as the king's two-square move,
+    /// and as the king capturing its own rook like in Chess960
+    fn castling_lans(&self, mv: &str) -> Option<(&'static str, &'static str)> {
+        match (mv, self.side_to_move()) {
+            ("O-O", Color::White) => Some(("e1g1", "e1h1")),
+            ("O-O-O", Color::White) => Some(("e1c1", "e1a1")),
+            ("O-O", Color::Black) => Some(("e8g8", "e8h8")),
+            ("O-O-O", Color::Black) => Some(("e8c8", "e8a8")),
+            _ => None,
+        }
+    }
+}
+
+impl Position for MockPosition {
+    type Move = &'static str;
+    type ReverseMove = ();
+    type Settings = ();
+
+    fn start_position_with_settings(_settings: &()) -> Self {
+        MockPosition {
+            supports_null_move: true,
+            moves: vec![],
+        }
+    }
+
+    fn side_to_move(&self) -> Color {
+        match self.moves.len() % 2 {
+            0 => Color::White,
+            _ => Color::Black,
+        }
+    }
+
+    fn generate_moves<E: Extend<Self::Move>>(&self, moves: &mut E) {
+        if self.game_result().is_none() {
+            moves.extend(LEGAL_MOVES.iter().copied());
+        }
+    }
+
+    fn do_move(&mut self, mv: Self::Move) {
+        self.moves.push(mv);
+    }
+
+    fn reverse_move(&mut self, _mv: ()) {
+        self.moves.pop();
+    }
+
+    fn game_result(&self) -> Option<GameResult> {
+        match (self.moves.last(), self.side_to_move()) {
+            (Some(&"mate"), Color::Black) => Some(GameResult::WhiteWin),
+            (Some(&"mate"), Color::White) => Some(GameResult::BlackWin),
+            (Some(&"draw"), _) => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+}
+
+impl PgnPosition for MockPosition {
+    const REQUIRED_TAGS: &'static [(&'static str, &'static str)] = &[
+        ("Event", "?"),
+        ("White", "?"),
+        ("Black", "?"),
+        ("Result", "*"),
+    ];
+
+    const START_POSITION_TAG_NAME: Option<&'static str> = Some("FEN");
+
+    fn full_move_number(&self) -> Option<u32> {
+        Some(self.moves.len() as u32 / 2 + 1)
+    }
+
+    fn from_fen_with_settings(fen: &str, _settings: &()) -> Result<Self, Error> {
+        let mut tokens = fen.split_whitespace();
+        let supports_null_move = match tokens.next() {
+            Some("start") => true,
+            Some("nonull") => false,
+            _ => return Err(Error::new_parse_error(format!("Invalid fen {}", fen))),
+        };
+        let moves = tokens
+            .filter(|token| !token.chars().all(|c| c.is_ascii_digit()))
+            .map(|token| match token {
+                NULL_MOVE => Ok(NULL_MOVE),
+                _ => Self::parse_san(token, "", ""),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(MockPosition {
+            supports_null_move,
+            moves,
+        })
+    }
+
+    fn to_fen(&self) -> String {
+        let start = if self.supports_null_move {
+            "start"
+        } else {
+            "nonull"
+        };
+        let mut fen = start.to_string();
+        for mv in self.moves.iter() {
+            fen.push(' ');
+            fen.push_str(mv);
+        }
+        fen
+    }
+
+    fn null_move(&self) -> Option<Self::Move> {
+        if self.supports_null_move {
+            Some(NULL_MOVE)
+        } else {
+            None
+        }
+    }
+
+    fn move_from_san(&self, input: &str) -> Result<Self::Move, Error> {
+        Self::parse_san(input, Self::CHECK_SUFFIX, Self::CHECKMATE_SUFFIX)
+    }
+
+    fn move_to_san(&self, mv: &Self::Move) -> String {
+        Self::write_san(mv, Self::CHECK_SUFFIX, Self::CHECKMATE_SUFFIX)
+    }
+
+    /// Castling moves are written as king moves, and other moves as in standard algebraic notation
+    fn move_from_lan(&self, input: &str) -> Result<Self::Move, Error> {
+        let is_castling = |mv: &str| match self.castling_lans(mv) {
+            Some((lan, chess960_lan)) => input == lan || input == chess960_lan,
+            None => false,
+        };
+        match ["O-O", "O-O-O"].iter().copied().find(|mv| is_castling(mv)) {
+            Some(mv) => Ok(mv),
+            None => self.move_from_san(input),
+        }
+    }
+
+    fn move_to_lan(&self, mv: &Self::Move) -> String {
+        match self.castling_lans(mv) {
+            Some((lan, _)) => lan.to_string(),
+            None => self.move_to_san(mv),
+        }
+    }
+}
+
+/// A variant of the mock game with its own result tokens and check suffixes.
+///
+/// Wins by resignation have their own tokens, which are written with en dashes, and draws cannot be written at all.
+/// Checks are written as `^` and checkmates as `^^`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub(crate) struct CustomPosition(pub(crate) MockPosition);
+
+impl Position for CustomPosition {
+    type Move = &'static str;
+    type ReverseMove = ();
+    type Settings = ();
+
+    fn start_position_with_settings(settings: &()) -> Self {
+        CustomPosition(MockPosition::start_position_with_settings(settings))
+    }
+
+    fn side_to_move(&self) -> Color {
+        self.0.side_to_move()
+    }
+
+    fn generate_moves<E: Extend<Self::Move>>(&self, moves: &mut E) {
+        self.0.generate_moves(moves)
+    }
+
+    fn do_move(&mut self, mv: Self::Move) {
+        self.0.do_move(mv)
+    }
+
+    fn reverse_move(&mut self, mv: ()) {
+        self.0.reverse_move(mv)
+    }
+
+    fn game_result(&self) -> Option<GameResult> {
+        self.0.game_result()
+    }
+}
+
+impl PgnPosition for CustomPosition {
+    const REQUIRED_TAGS: &'static [(&'static str, &'static str)] = MockPosition::REQUIRED_TAGS;
+
+    const START_POSITION_TAG_NAME: Option<&'static str> = MockPosition::START_POSITION_TAG_NAME;
+
+    const POSSIBLE_GAME_RESULTS: &'static [(&'static str, Option<GameResult>)] = &[
+        ("*", None),
+        ("1-0", Some(GameResult::WhiteWin)),
+        ("0-1", Some(GameResult::BlackWin)),
+        ("R\u{2013}0", Some(GameResult::WhiteWin)),
+        ("0\u{2013}R", Some(GameResult::BlackWin)),
+    ];
+
+    const CHECK_SUFFIX: &'static str = "^";
+
+    const CHECKMATE_SUFFIX: &'static str = "^^";
+
+    fn full_move_number(&self) -> Option<u32> {
+        self.0.full_move_number()
+    }
+
+    fn from_fen_with_settings(fen: &str, settings: &()) -> Result<Self, Error> {
+        MockPosition::from_fen_with_settings(fen, settings).map(CustomPosition)
+    }
+
+    fn to_fen(&self) -> String {
+        self.0.to_fen()
+    }
+
+    fn move_from_san(&self, input: &str) -> Result<Self::Move, Error> {
+        MockPosition::parse_san(input, Self::CHECK_SUFFIX, Self::CHECKMATE_SUFFIX)
+    }
+
+    fn move_to_san(&self, mv: &Self::Move) -> String {
+        MockPosition::write_san(mv, Self::CHECK_SUFFIX, Self::CHECKMATE_SUFFIX)
+    }
+
+    fn move_from_lan(&self, input: &str) -> Result<Self::Move, Error> {
+        self.move_from_san(input)
+    }
+
+    fn move_to_lan(&self, mv: &Self::Move) -> String {
+        self.move_to_san(mv)
+    }
+}
+
+/// Writes a game with `write_pgn`, returning the output as a string
+pub(crate) fn write<P: PgnPosition + Clone>(game: &PgnGame<P>) -> String {
+    let mut output = vec![];
+    write_pgn(game, &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
@@ -0,0 +1,909 @@
+//! Streaming parser for pgn files, which reports the contents of each game to a [`Visitor`].
+//!
+//! [`Visitor`]: trait.Visitor.html
+
+use crate::{
+    annotation_to_nag, normalize_result_token, null_move_from_san, Error, ErrorKind, PgnPosition,
+};
+use board_game_traits::GameResult;
+use std::borrow::Cow;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::str;
+use std::string::FromUtf8Error;
+
+/// Callbacks for the contents of pgn games, used with [`parse_stream`].
+///
+/// All methods have empty default implementations, so implementors only need to handle the events they are interested in.
+/// For each game, `begin_game` is called first, then `tag` for each tag pair, then `start_position`, then the movetext events in order, and finally `end_game`.
+///
+/// [`parse_stream`]: fn.parse_stream.html
+pub trait Visitor<P: PgnPosition> {
+    /// Called at the start of each game, before its tags
+    fn begin_game(&mut self) {}
+
+    /// Called for each tag pair, in the order they appear in the game
+    fn tag(&mut self, _key: &str, _value: &str) {}
+
+    /// Called after the tags, with the position that the game starts from
+    fn start_position(&mut self, _position: &P) {}
+
+    /// Called for each move, after it has been resolved and checked for legality in the position where it is played
+    fn san_move(&mut self, _mv: &P::Move) {}
+
+    /// Called for each comment, with the text between the delimiters
+    /// The text is passed verbatim, including any newlines and surrounding whitespace
+    fn comment(&mut self, _comment: &str) {}
+
+    /// Called for each [Numeric Annotation Glyph][1], including those written as suffix annotations like `!?`
+    ///
+    /// [1]: https://en.wikipedia.org/wiki/Numeric_Annotation_Glyphs
+    fn nag(&mut self, _nag: u8) {}
+
+    /// Called at the start of a variation. The variation is an alternative to the previous move.
+    fn begin_variation(&mut self) {}
+
+    /// Called at the end of a variation
+    fn end_variation(&mut self) {}
+
+    /// Called at the end of each game, with the result from its terminating token
+    /// The result is `None` for unfinished games, and for games without a result token
+    fn end_game(&mut self, _result: Option<GameResult>) {}
+}
+
+/// Parses all pgn games from a reader, reporting their contents to the visitor.
+///
+/// This does not store any games, which makes it suitable for processing large files.
+/// Whitespace, comments and escape lines between a game's result token and the start of the next game are ignored,
+/// but a game without tag pairs must be the first game in the input, because it cannot otherwise be told apart from the previous game.
+/// Parsing stops at the first error, which has the game's index and the line and span of the error in the input.
+pub fn parse_stream<R: Read, P>(reader: R, visitor: &mut dyn Visitor<P>) -> Result<(), Error>
+where
+    P: PgnPosition + Clone,
+{
+    parse_games(GameTexts::new(BufReader::new(reader)), visitor)
+}
+
+/// Character encodings for pgn input, used with [`parse_bytes`] and [`PgnReader::from_bytes`].
+///
+/// [`parse_bytes`]: fn.parse_bytes.html
+/// [`PgnReader::from_bytes`]: struct.PgnReader.html#method.from_bytes
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Encoding {
+    /// UTF-8, with or without a byte order mark
+    Utf8,
+    /// ISO 8859-1, which is the encoding required by the pgn standard
+    Latin1,
+}
+
+/// Parses all pgn games from an in-memory buffer with the given encoding, reporting their contents to the visitor.
+///
+/// Bytes that are invalid in the encoding produce an error with their byte offset, before any games are parsed.
+/// Otherwise, this behaves like [`parse_stream`].
+///
+/// [`parse_stream`]: fn.parse_stream.html
+pub fn parse_bytes<P>(
+    bytes: &[u8],
+    encoding: Encoding,
+    visitor: &mut dyn Visitor<P>,
+) -> Result<(), Error>
+where
+    P: PgnPosition + Clone,
+{
+    let input = decode(bytes, encoding)?;
+    parse_games(GameTexts::new(input.as_bytes()), visitor)
+}
+
+fn parse_games<R: BufRead, P>(
+    mut games: GameTexts<R>,
+    visitor: &mut dyn Visitor<P>,
+) -> Result<(), Error>
+where
+    P: PgnPosition + Clone,
+{
+    while let Some(game) = games.next() {
+        let game = game?;
+        parse_game(&game, visitor).map_err(|err| games.locate_error(&game, err))?;
+    }
+    Ok(())
+}
+
+fn decode(bytes: &[u8], encoding: Encoding) -> Result<Cow<'_, str>, Error> {
+    match encoding {
+        Encoding::Utf8 => str::from_utf8(bytes).map(Cow::Borrowed).map_err(|err| {
+            Error::new_caused_by(
+                ErrorKind::EncodingError,
+                format!("Invalid UTF-8 at byte {}", err.valid_up_to()),
+                err,
+            )
+        }),
+        Encoding::Latin1 => Ok(Cow::Owned(decode_latin1(bytes))),
+    }
+}
+
+/// Decodes ISO 8859-1, where every byte is the character with the same code point
+pub(crate) fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Finds the boundaries between games, from the lines of a pgn file.
+///
+/// A new game starts at the first tag pair line after the previous game's movetext.
+#[derive(Default)]
+pub(crate) struct GameSplitter {
+    has_movetext: bool,
+    in_comment: bool,
+}
+
+impl GameSplitter {
+    /// Returns whether the line starts a new game. Must be called for every line, in order.
+    pub(crate) fn starts_new_game(&mut self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        if !self.in_comment && trimmed.starts_with('[') {
+            if self.has_movetext {
+                self.has_movetext = false;
+                return true;
+            }
+        } else {
+            if !self.in_comment && !trimmed.is_empty() && !trimmed.starts_with('%') {
+                self.has_movetext = true;
+            }
+            self.in_comment = ends_inside_comment(line, self.in_comment);
+        }
+        false
+    }
+}
+
+/// The UTF-8 byte order mark, which some programs write at the start of pgn files
+pub(crate) const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// An iterator over the text of each game in a pgn file.
+///
+/// A byte order mark at the start of the input is skipped.
+pub(crate) struct GameTexts<R> {
+    reader: R,
+    splitter: GameSplitter,
+    next_line: Option<String>,
+    /// An encoding error in `next_line`
+    next_encoding_error: Option<Error>,
+    /// The number of games returned so far
+    games_read: usize,
+    lines_read: usize,
+    bytes_read: usize,
+    /// The line number of the first line of the most recently returned game, starting at 1
+    start_line: usize,
+    next_start_line: usize,
+    /// The byte offset in the input of the most recently returned game
+    start_byte: usize,
+    next_start_byte: usize,
+}
+
+impl<R: BufRead> GameTexts<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        GameTexts {
+            reader,
+            splitter: GameSplitter::default(),
+            next_line: None,
+            next_encoding_error: None,
+            games_read: 0,
+            lines_read: 0,
+            bytes_read: 0,
+            start_line: 1,
+            next_start_line: 1,
+            start_byte: 0,
+            next_start_byte: 0,
+        }
+    }
+
+    /// Adds the game's index and the error's line to an error from parsing the most recently returned game, whose text is `game`.
+    ///
+    /// The error's span is moved from the game's text to the whole input. Without a span, the line is the one where the game starts.
+    pub(crate) fn locate_error(&self, game: &str, err: Error) -> Error {
+        let game_index = self.games_read - 1;
+        let (line, span) = match err.span() {
+            Some(span) => {
+                let lines_before_span = game[..span.start.min(game.len())].matches('\n').count();
+                (
+                    self.start_line + lines_before_span,
+                    Some(self.start_byte + span.start..self.start_byte + span.end),
+                )
+            }
+            None => (self.start_line, None),
+        };
+        let located_err = Error::new_caused_by(
+            err.kind,
+            format!("Failed to parse game {}, at line {}", game_index + 1, line),
+            err,
+        )
+        .with_location(game_index, line);
+        match span {
+            Some(span) => located_err.with_span(span),
+            None => located_err,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for GameTexts<R> {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Result<String, Error>> {
+        let mut game = self.next_line.take().unwrap_or_default();
+        let mut encoding_error = self.next_encoding_error.take();
+        self.start_line = self.next_start_line;
+        self.start_byte = self.next_start_byte;
+
+        loop {
+            let mut bytes = vec![];
+            match self.reader.read_until(b'\n', &mut bytes) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(err) => return Some(Err(io_error(err))),
+            }
+            self.lines_read += 1;
+            let line_start = self.bytes_read;
+            self.bytes_read += bytes.len();
+            // Invalid lines are still used to find the end of the game, so that the following games can be read
+            let (mut line, invalid_utf8) = match String::from_utf8(bytes) {
+                Ok(line) => (line, None),
+                Err(err) => (
+                    String::from_utf8_lossy(err.as_bytes()).into_owned(),
+                    Some(err),
+                ),
+            };
+            if self.lines_read == 1 && line.starts_with(BYTE_ORDER_MARK) {
+                line.replace_range(..BYTE_ORDER_MARK.len_utf8(), "");
+                self.start_byte += BYTE_ORDER_MARK.len_utf8();
+            }
+            let starts_new_game = self.splitter.starts_new_game(&line);
+            if let Some(err) = invalid_utf8 {
+                let game_index = self.games_read + starts_new_game as usize;
+                let err = self.encoding_error(err, game_index, line_start);
+                if starts_new_game {
+                    self.next_encoding_error = Some(err);
+                } else {
+                    encoding_error.get_or_insert(err);
+                }
+            }
+            if starts_new_game {
+                self.next_start_line = self.lines_read;
+                self.next_start_byte = line_start;
+                self.next_line = Some(line);
+                self.games_read += 1;
+                return Some(encoding_error.map_or(Ok(game), Err));
+            }
+            game.push_str(&line);
+        }
+
+        if game.trim().is_empty() {
+            None
+        } else {
+            self.games_read += 1;
+            Some(encoding_error.map_or(Ok(game), Err))
+        }
+    }
+}
+
+impl<R> GameTexts<R> {
+    /// Returns the error for invalid UTF-8 in the most recently read line, which starts at byte `line_start` of the input
+    fn encoding_error(&self, err: FromUtf8Error, game_index: usize, line_start: usize) -> Error {
+        let position = line_start + err.utf8_error().valid_up_to();
+        Error::new_caused_by(
+            ErrorKind::EncodingError,
+            format!(
+                "Invalid UTF-8 in game {}, at line {}",
+                game_index + 1,
+                self.lines_read
+            ),
+            err,
+        )
+        .with_location(game_index, self.lines_read)
+        .with_span(position..position + 1)
+    }
+}
+
+fn io_error(err: io::Error) -> Error {
+    if err.kind() == io::ErrorKind::InvalidData {
+        Error::new_caused_by(
+            ErrorKind::EncodingError,
+            "Pgn input is not valid UTF-8".to_string(),
+            err,
+        )
+    } else {
+        Error::new_caused_by(
+            ErrorKind::IoError,
+            "Failed to read pgn input".to_string(),
+            err,
+        )
+    }
+}
+
+/// Returns whether a line of movetext ends inside a brace comment
+fn ends_inside_comment(line: &str, mut in_comment: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '}' if in_comment => in_comment = false,
+            '{' if !in_comment => in_comment = true,
+            ';' if !in_comment => break,
+            _ => (),
+        }
+    }
+    in_comment
+}
+
+/// Parses the text of a single game, reporting its contents to the visitor.
+pub(crate) fn parse_game<P>(input: &str, visitor: &mut dyn Visitor<P>) -> Result<(), Error>
+where
+    P: PgnPosition + Clone,
+{
+    let mut offset = 0;
+    let mut start_position_fen = None;
+    let mut has_setup_tag = false;
+
+    visitor.begin_game();
+
+    loop {
+        offset = skip_whitespace(input, offset);
+        if !input[offset..].starts_with('[') {
+            break;
+        }
+        let (name, value, end) = parse_tag(input, offset)?;
+        if P::START_POSITION_TAG_NAME == Some(name) {
+            start_position_fen = Some(value.to_string());
+        } else if name == "SetUp" && value == "1" {
+            has_setup_tag = true;
+        }
+        visitor.tag(name, &value);
+        offset = end;
+    }
+
+    let mut position = match (start_position_fen, P::START_POSITION_TAG_NAME) {
+        (Some(fen), _) => P::from_fen(&fen).map_err(|err| {
+            Error::new_caused_by(
+                ErrorKind::IllegalPosition,
+                format!("Invalid start position \"{}\"", fen),
+                err,
+            )
+        })?,
+        (None, Some(tag_name)) if has_setup_tag => {
+            return Err(Error::new_parse_error(format!(
+                "Game has a SetUp tag, but no {} tag",
+                tag_name
+            )))
+        }
+        (None, _) => P::start_position(),
+    };
+    visitor.start_position(&position);
+    // The position before the last move, which is where a variation starts
+    let mut last_position: Option<P> = None;
+    let mut variation_stack: Vec<(P, Option<P>)> = vec![];
+
+    loop {
+        offset = skip_whitespace(input, offset);
+        let c = match input[offset..].chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        match c {
+            '{' => {
+                let end = input[offset..].find('}').ok_or_else(|| {
+                    Error::new_parse_error_at(
+                        format!("Unterminated comment at byte {}", offset),
+                        offset..input.len(),
+                    )
+                })? + offset;
+                visitor.comment(&input[offset + 1..end]);
+                offset = end + 1;
+            }
+            ';' => {
+                let end = input[offset..]
+                    .find('\n')
+                    .map_or(input.len(), |i| i + offset);
+                visitor.comment(input[offset + 1..end].trim_end_matches('\r'));
+                offset = end;
+            }
+            '%' if offset == 0 || input[..offset].ends_with('\n') => {
+                offset = input[offset..]
+                    .find('\n')
+                    .map_or(input.len(), |i| i + offset);
+            }
+            '(' => {
+                let variation_start = last_position.take().ok_or_else(|| {
+                    Error::new_parse_error_at(
+                        format!("Variation without a preceding move at byte {}", offset),
+                        offset..offset + 1,
+                    )
+                })?;
+                variation_stack.push((position, Some(variation_start.clone())));
+                position = variation_start;
+                visitor.begin_variation();
+                offset += 1;
+            }
+            ')' => {
+                let (outer_position, outer_last_position) =
+                    variation_stack.pop().ok_or_else(|| {
+                        Error::new_parse_error_at(
+                            format!("Unmatched ')' at byte {}", offset),
+                            offset..offset + 1,
+                        )
+                    })?;
+                position = outer_position;
+                last_position = outer_last_position;
+                visitor.end_variation();
+                offset += 1;
+            }
+            '$' => {
+                let end = input[offset + 1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .map_or(input.len(), |i| i + offset + 1);
+                let nag = input[offset + 1..end].parse().map_err(|err| {
+                    Error::new_caused_by(
+                        ErrorKind::ParseError,
+                        format!("Invalid NAG at byte {}", offset),
+                        err,
+                    )
+                    .with_span(offset..end)
+                })?;
+                visitor.nag(nag);
+                offset = end;
+            }
+            _ => {
+                let end = input[offset..]
+                    .find(|c: char| c.is_whitespace() || "{}();$".contains(c))
+                    .map_or(input.len(), |i| i + offset);
+                if end == offset {
+                    return Err(Error::new_parse_error_at(
+                        format!("Unexpected '{}' at byte {}", c, offset),
+                        offset..offset + c.len_utf8(),
+                    ));
+                }
+                let token = &input[offset..end];
+
+                if let Some(result) = game_result_from_token::<P>(token) {
+                    if !variation_stack.is_empty() {
+                        return Err(Error::new_parse_error_at(
+                            format!(
+                                "Game result \"{}\" inside variation at byte {}",
+                                token, offset
+                            ),
+                            offset..end,
+                        ));
+                    }
+                    check_trailing_content(input, end)?;
+                    visitor.end_game(result);
+                    return Ok(());
+                } else {
+                    // Move numbers may be written directly before the move, like `1.e4`
+                    let move_number_length = move_number_prefix_length(token);
+                    if move_number_length < token.len() {
+                        let san_offset = offset + move_number_length;
+                        let (san, annotations) =
+                            split_move_annotations::<P>(&input[san_offset..end]);
+                        let mv = parse_legal_move(&position, san, san_offset)?;
+                        visitor.san_move(&mv);
+                        for nag in annotations.into_iter().filter_map(annotation_to_nag) {
+                            visitor.nag(nag);
+                        }
+                        last_position = Some(position.clone());
+                        position.do_move(mv);
+                    }
+                }
+                offset = end;
+            }
+        }
+    }
+
+    if !variation_stack.is_empty() {
+        return Err(Error::new_parse_error(format!(
+            "{} unclosed variation(s) at end of game",
+            variation_stack.len()
+        )));
+    }
+    visitor.end_game(None);
+    Ok(())
+}
+
+/// Checks that the content after a game's result token, starting at `offset`, is only whitespace, comments and escape lines.
+///
+/// Anything else is an error, because it would be the movetext of a following game without tag pairs, which cannot be split from this one.
+fn check_trailing_content(input: &str, mut offset: usize) -> Result<(), Error> {
+    loop {
+        offset = skip_whitespace(input, offset);
+        let rest = &input[offset..];
+        let is_line_start = offset == 0 || input[..offset].ends_with('\n');
+        if rest.is_empty() {
+            return Ok(());
+        } else if rest.starts_with('{') {
+            offset = rest.find('}').ok_or_else(|| {
+                Error::new_parse_error_at(
+                    format!("Unterminated comment at byte {}", offset),
+                    offset..input.len(),
+                )
+            })? + offset
+                + 1;
+        } else if rest.starts_with(';') || (rest.starts_with('%') && is_line_start) {
+            offset = rest.find('\n').map_or(input.len(), |i| i + offset);
+        } else {
+            let end = rest
+                .find(char::is_whitespace)
+                .map_or(input.len(), |i| i + offset);
+            return Err(Error::new_parse_error_at(
+                format!(
+                    "Unexpected \"{}\" after game result at byte {}",
+                    &input[offset..end],
+                    offset
+                ),
+                offset..end,
+            ));
+        }
+    }
+}
+
+pub(crate) fn skip_whitespace(input: &str, offset: usize) -> usize {
+    input[offset..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(input.len(), |i| i + offset)
+}
+
+/// Parses a tag pair starting at `offset`, returning its name, its unescaped value and the offset after the tag
+///
+/// Tag names may contain any non-whitespace characters except `"` and `]`, so that non-standard names found in real databases are preserved exactly.
+pub(crate) fn parse_tag(input: &str, offset: usize) -> Result<(&str, Cow<'_, str>, usize), Error> {
+    let error = |message: &str, at: usize| {
+        let char_length = input[at..].chars().next().map_or(0, char::len_utf8);
+        Error::new_parse_error_at(
+            format!("{} in tag pair at byte {}", message, at),
+            at..at + char_length,
+        )
+    };
+    let name_start = skip_whitespace(input, offset + 1);
+    let name_end = input[name_start..]
+        .find(|c: char| c.is_whitespace() || c == '"' || c == ']')
+        .map_or(input.len(), |i| i + name_start);
+    if name_start == name_end {
+        return Err(error("Missing tag name", name_start));
+    }
+    let value_start = skip_whitespace(input, name_end);
+    if !input[value_start..].starts_with('"') {
+        return Err(error("Expected '\"'", value_start));
+    }
+
+    // Only allocate if the value contains escaped characters
+    let mut unescaped_value: Option<String> = None;
+    let mut chars = input[value_start + 1..].char_indices();
+    let value_end = loop {
+        match chars.next() {
+            Some((i, '\\')) => match chars.next() {
+                Some((_, c)) => unescaped_value
+                    .get_or_insert_with(|| input[value_start + 1..value_start + 1 + i].to_string())
+                    .push(c),
+                None => return Err(error("Unterminated value", value_start)),
+            },
+            Some((i, '"')) => break value_start + 1 + i,
+            Some((_, c)) => {
+                if let Some(value) = unescaped_value.as_mut() {
+                    value.push(c);
+                }
+            }
+            None => return Err(error("Unterminated value", value_start)),
+        }
+    };
+
+    let closing_bracket = skip_whitespace(input, value_end + 1);
+    if !input[closing_bracket..].starts_with(']') {
+        return Err(error("Expected ']'", closing_bracket));
+    }
+    let value = match unescaped_value {
+        Some(value) => Cow::Owned(value),
+        None => Cow::Borrowed(&input[value_start + 1..value_end]),
+    };
+    Ok((&input[name_start..name_end], value, closing_bracket + 1))
+}
+
+/// Returns the game result for a result token, or `None` if the token is not a result
+///
+/// The token is looked up as written first, so that custom result tokens are never altered by the normalization.
+pub(crate) fn game_result_from_token<P: PgnPosition>(token: &str) -> Option<Option<GameResult>> {
+    P::parse_result(token).or_else(|| match normalize_result_token(token) {
+        Cow::Owned(normalized_token) => P::parse_result(&normalized_token),
+        Cow::Borrowed(_) => None,
+    })
+}
+
+/// Returns the length of the move number indication at the start of a token, like `1.` or `12...`, or 0 if there is none
+///
+/// A token of only dots, like the `...` in `1. ... e5`, is entirely a move number indication.
+fn move_number_prefix_length(token: &str) -> usize {
+    let digits_end = token
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(token.len());
+    let dots_end = token[digits_end..]
+        .find(|c: char| c != '.')
+        .map_or(token.len(), |i| i + digits_end);
+    if dots_end > digits_end && (digits_end > 0 || dots_end == token.len()) {
+        dots_end
+    } else {
+        0
+    }
+}
+
+/// Splits the move annotations from the end of a move token
+fn split_move_annotations<P: PgnPosition>(token: &str) -> (&str, Vec<&str>) {
+    let mut san = token;
+    let mut annotations = vec![];
+    while let Some(annotation) = P::POSSIBLE_MOVE_ANNOTATIONS
+        .iter()
+        .find(|annotation| san.len() > annotation.len() && san.ends_with(*annotation))
+    {
+        san = &san[..san.len() - annotation.len()];
+        annotations.push(*annotation);
+    }
+    annotations.reverse();
+    (san, annotations)
+}
+
+fn parse_legal_move<P: PgnPosition>(
+    position: &P,
+    san: &str,
+    offset: usize,
+) -> Result<P::Move, Error> {
+    if let Some(null_move) = null_move_from_san(position, san) {
+        return null_move.map_err(|err| {
+            Error::new_caused_by(
+                err.kind,
+                format!("Invalid move \"{}\" at byte {}", san, offset),
+                err,
+            )
+            .with_span(offset..offset + san.len())
+        });
+    }
+    let mv = position.move_from_san(san).map_err(|err| {
+        Error::new_caused_by(
+            err.kind,
+            format!("Invalid move \"{}\" at byte {}", san, offset),
+            err,
+        )
+        .with_span(offset..offset + san.len())
+    })?;
+    let mut legal_moves = vec![];
+    position.generate_moves(&mut legal_moves);
+    if legal_moves.contains(&mv) {
+        Ok(mv)
+    } else {
+        Err(Error::new(
+            ErrorKind::IllegalMove,
+            format!("Illegal move \"{}\" at byte {}", san, offset),
+        )
+        .with_span(offset..offset + san.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockPosition, LEGAL_MOVES};
+
+    /// Records the events of a parse as strings
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl Visitor<MockPosition> for Recorder {
+        fn begin_game(&mut self) {
+            self.events.push("begin".to_string());
+        }
+
+        fn tag(&mut self, key: &str, value: &str) {
+            self.events.push(format!("[{} {}]", key, value));
+        }
+
+        fn start_position(&mut self, position: &MockPosition) {
+            self.events.push(format!("<{}>", position.to_fen()));
+        }
+
+        fn san_move(&mut self, mv: &&'static str) {
+            self.events.push(mv.to_string());
+        }
+
+        fn comment(&mut self, comment: &str) {
+            self.events.push(format!("{{{}}}", comment));
+        }
+
+        fn nag(&mut self, nag: u8) {
+            self.events.push(format!("${}", nag));
+        }
+
+        fn begin_variation(&mut self) {
+            self.events.push("(".to_string());
+        }
+
+        fn end_variation(&mut self) {
+            self.events.push(")".to_string());
+        }
+
+        fn end_game(&mut self, result: Option<GameResult>) {
+            self.events.push(format!("end {:?}", result));
+        }
+    }
+
+    fn events(input: &str) -> Result<Vec<String>, Error> {
+        let mut recorder = Recorder::default();
+        parse_stream(input.as_bytes(), &mut recorder)?;
+        Ok(recorder.events)
+    }
+
+    #[test]
+    fn whitespace_comments_and_escape_lines_after_result_are_ignored() {
+        let input =
+            "[Event \"1\"]\n1. e4 1-0 \n{Trailing\ncomment} ; Line comment\n%escape line\n\n\
+                     [Event \"2\"]\n1. d4 *\n";
+        let game_events = events(input).unwrap();
+        assert!(!game_events.iter().any(|event| event.contains("comment")));
+        assert_eq!(
+            game_events
+                .iter()
+                .filter(|event| event.starts_with("end"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn movetext_after_result_is_an_error() {
+        let input = "1. e4 e5 *\n\n1. d4 *\n\n1. Nf3 *\n";
+        let err = events(input).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+        assert_eq!(&input[err.span().unwrap()], "1.");
+
+        let err = events("[Event \"1\"]\n1. e4 1-0 e5\n").unwrap_err();
+        assert_eq!(err.span(), Some(22..24));
+        assert!(events("1. e4 1-0 {unterminated").is_err());
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        let input = "\u{feff}[Event \"1\"]\n1. e4 *\n";
+        assert_eq!(events(input).unwrap()[1], "[Event 1]");
+
+        let mut recorder = Recorder::default();
+        parse_bytes(input.as_bytes(), Encoding::Utf8, &mut recorder).unwrap();
+        assert_eq!(recorder.events[1], "[Event 1]");
+
+        let err = parse_bytes::<MockPosition>(
+            b"\xEF\xBB\xBF1. e4 \xFF *",
+            Encoding::Utf8,
+            &mut Recorder::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EncodingError);
+        assert!(err.to_string().contains("at byte 9"), "{}", err);
+    }
+
+    #[test]
+    fn move_numbers_without_spaces() {
+        let moves = |input| {
+            events(input)
+                .unwrap()
+                .into_iter()
+                .filter(|event| LEGAL_MOVES.contains(&event.as_str()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(moves("1.e4 e5 2.Nf3 Nc6 *"), vec!["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(moves("1.e4 1...e5 2.Nf3 *"), vec!["e4", "e5", "Nf3"]);
+        assert_eq!(
+            moves("[SetUp \"1\"]\n[FEN \"start e4\"]\n\n1...Nf6 2.d4 *"),
+            vec!["Nf6", "d4"]
+        );
+    }
+
+    #[test]
+    fn separate_dots_after_move_number() {
+        let input = "[SetUp \"1\"]\n[FEN \"start e4\"]\n\n1. ... e5 2. Nf3 2. ... Nc6 *";
+        let game_events = events(input).unwrap();
+        assert_eq!(
+            game_events[3..],
+            ["<start e4>", "e5", "Nf3", "Nc6", "end None"]
+        );
+    }
+
+    #[test]
+    fn unmatched_closing_brace_is_an_error() {
+        for input in ["1. e4 } e5 *", "}", "1. e4 (1... e5 }) *"] {
+            let err = events(input).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ParseError);
+            assert_eq!(&input[err.span().unwrap()], "}", "{}", input);
+        }
+    }
+
+    #[test]
+    fn events_are_reported_in_order() {
+        let input = "[Event \"Test \\\"quoted\\\"\"]\n[White \"A\"]\n\n\
+                     {Start} 1. e4 $1 e5!? (1... d5 ; Scandinavian\n2. Nf3) 2. Nf3 1-0\n";
+        assert_eq!(
+            events(input).unwrap(),
+            vec![
+                "begin",
+                "[Event Test \"quoted\"]",
+                "[White A]",
+                "<start>",
+                "{Start}",
+                "e4",
+                "$1",
+                "e5",
+                "$5",
+                "(",
+                "d5",
+                "{ Scandinavian}",
+                "Nf3",
+                ")",
+                "Nf3",
+                "end Some(WhiteWin)",
+            ]
+        );
+    }
+
+    #[test]
+    fn games_are_split_at_tag_pairs() {
+        let input =
+            "[Event \"1\"]\n1. e4 *\n\n[Event \"2\"]\n1. d4 d5\n\n[Event \"3\"]\n1. Nf3 0-1\n";
+        let game_events = events(input).unwrap();
+        let ends: Vec<&String> = game_events
+            .iter()
+            .filter(|e| e.starts_with("end"))
+            .collect();
+        assert_eq!(ends, vec!["end None", "end None", "end Some(BlackWin)"]);
+    }
+
+    #[test]
+    fn moves_after_the_game_ends_are_illegal() {
+        let err = events("1. mate e5 1-0").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+    }
+
+    #[test]
+    fn start_position_from_fen_tag() {
+        let game_events = events("[SetUp \"1\"]\n[FEN \"start e4\"]\n\n1... e5 *\n").unwrap();
+        assert!(game_events.contains(&"<start e4>".to_string()));
+
+        let err = events("[SetUp \"1\"]\n\n1. e4 *\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+
+        let err = events("[FEN \"invalid\"]\n\n1. e4 *\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalPosition);
+    }
+
+    #[test]
+    fn errors_have_spans() {
+        let input = "1. e4 Ke2 *";
+        let err = events(input).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert_eq!(&input[err.span().unwrap()], "Ke2");
+
+        let input = "[Event \"Test]\n1. e4 *";
+        let err = events(input).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ParseError);
+        assert_eq!(err.span(), Some(7..8));
+
+        for (input, span) in [
+            ("1. e4 (e5", None),
+            ("1. e4 e5) *", Some(8..9)),
+            ("(1. e4) *", Some(0..1)),
+        ] {
+            assert_eq!(events(input).unwrap_err().span(), span, "{}", input);
+        }
+    }
+
+    #[test]
+    fn errors_in_later_games_have_spans_in_the_input() {
+        let input = "[Event \"1\"]\n1. e4 *\n\n[Event \"2\"]\n1. e4\nKe2 *\n";
+        let err = events(input).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert_eq!(&input[err.span().unwrap()], "Ke2");
+        assert_eq!(err.game_index(), Some(1));
+        assert_eq!(err.line(), Some(6));
+
+        let mut recorder = Recorder::default();
+        let err = parse_bytes(input.as_bytes(), Encoding::Latin1, &mut recorder).unwrap_err();
+        assert_eq!(&input[err.span().unwrap()], "Ke2");
+    }
+}
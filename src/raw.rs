@@ -0,0 +1,154 @@
+//! Zero-copy access to the games of an in-memory pgn file, without parsing their movetext.
+
+use crate::parser::{parse_game, parse_tag, skip_whitespace, GameSplitter, BYTE_ORDER_MARK};
+use crate::{Error, PgnPosition, Visitor};
+use std::borrow::Cow;
+
+/// Returns an iterator over the games in a pgn file, without parsing them.
+///
+/// Each game borrows its text from the input. Tags are only parsed when they are accessed,
+/// and the movetext is only parsed if the game is passed to a visitor with [`RawGame::parse`].
+/// A byte order mark at the start of the input is skipped.
+///
+/// [`RawGame::parse`]: struct.RawGame.html#method.parse
+pub fn raw_games(input: &str) -> RawGames<'_> {
+    RawGames {
+        input: input.strip_prefix(BYTE_ORDER_MARK).unwrap_or(input),
+        offset: 0,
+        splitter: GameSplitter::default(),
+    }
+}
+
+/// An iterator over the unparsed games in a pgn file.
+///
+/// This struct is created by [`raw_games`].
+///
+/// [`raw_games`]: fn.raw_games.html
+pub struct RawGames<'a> {
+    input: &'a str,
+    offset: usize,
+    splitter: GameSplitter,
+}
+
+impl<'a> Iterator for RawGames<'a> {
+    type Item = Result<RawGame<'a>, Error>;
+
+    fn next(&mut self) -> Option<Result<RawGame<'a>, Error>> {
+        let start = self.offset;
+        for line in self.input[start..].split_inclusive('\n') {
+            // The first line of a game may already have been seen as the end of the previous game
+            if self.splitter.starts_new_game(line) && self.offset > start {
+                break;
+            }
+            self.offset += line.len();
+        }
+        let text = &self.input[start..self.offset];
+        if text.trim().is_empty() {
+            None
+        } else {
+            Some(Ok(RawGame { text }))
+        }
+    }
+}
+
+/// The unparsed text of a single pgn game, borrowed from the input.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct RawGame<'a> {
+    text: &'a str,
+}
+
+impl<'a> RawGame<'a> {
+    /// The full text of the game, including its tags
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Returns an iterator over the game's tag pairs, which are parsed as they are iterated over.
+    ///
+    /// Tag values are only copied if they contain escaped characters.
+    pub fn tags(&self) -> RawTags<'a> {
+        RawTags {
+            text: self.text,
+            offset: 0,
+        }
+    }
+
+    /// Returns the value of the first tag with the given name, or `None` if the game has no such tag.
+    ///
+    /// Tag names are case-sensitive.
+    pub fn tag(&self, name: &str) -> Result<Option<Cow<'a, str>>, Error> {
+        for tag in self.tags() {
+            let (tag_name, value) = tag?;
+            if tag_name == name {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The movetext of the game, starting after its last tag pair
+    pub fn movetext(&self) -> &'a str {
+        let mut tags = self.tags();
+        while let Some(Ok(_)) = tags.next() {}
+        &self.text[skip_whitespace(self.text, tags.offset)..]
+    }
+
+    /// Fully parses the game, reporting its contents to the visitor.
+    pub fn parse<P>(&self, visitor: &mut dyn Visitor<P>) -> Result<(), Error>
+    where
+        P: PgnPosition + Clone,
+    {
+        parse_game(self.text, visitor)
+    }
+}
+
+/// An iterator over the tag pairs of a [`RawGame`].
+///
+/// This struct is created by [`RawGame::tags`].
+///
+/// [`RawGame`]: struct.RawGame.html
+/// [`RawGame::tags`]: struct.RawGame.html#method.tags
+pub struct RawTags<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Iterator for RawTags<'a> {
+    type Item = Result<(&'a str, Cow<'a, str>), Error>;
+
+    fn next(&mut self) -> Option<Result<(&'a str, Cow<'a, str>), Error>> {
+        let tag_start = skip_whitespace(self.text, self.offset);
+        if !self.text[tag_start..].starts_with('[') {
+            return None;
+        }
+        match parse_tag(self.text, tag_start) {
+            Ok((name, value, end)) => {
+                self.offset = end;
+                Some(Ok((name, value)))
+            }
+            Err(err) => {
+                self.offset = self.text.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPosition;
+    use crate::parse_pgn;
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        let input = "\u{feff}[Event \"1\"]\n1. e4 *\n\n[Event \"2\"]\n1. d4 *\n";
+        let games: Vec<RawGame> = raw_games(input).map(Result::unwrap).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].text(), "[Event \"1\"]\n1. e4 *\n\n");
+        assert_eq!(games[0].tag("Event").unwrap().as_deref(), Some("1"));
+
+        let game = parse_pgn::<MockPosition>("\u{feff}[Event \"1\"]\n1. e4 *\n").unwrap();
+        assert_eq!(game.tags.get("Event"), Some("1"));
+    }
+}
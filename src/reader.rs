@@ -0,0 +1,247 @@
+//! Iterator over the games in a pgn file.
+
+use crate::game::build_game;
+use crate::parser::{decode_latin1, GameTexts};
+use crate::{raw_games, Encoding, Error, ErrorKind, PgnGame, PgnPosition, Tags};
+use std::borrow::Cow;
+use std::io::{BufRead, Cursor};
+use std::marker::PhantomData;
+
+/// An iterator that parses the games of a pgn file one by one, without reading the whole file into memory.
+///
+/// Games are separated at the first tag pair after the previous game's movetext.
+/// If a game fails to parse or is not valid UTF-8, its error is yielded and the reader continues with the next game.
+/// Such errors include the game's index and the line in the input where the error occurred,
+/// which are available from [`Error::game_index`] and [`Error::line`]. Their span is relative to the whole input.
+/// Reading stops after an IO error.
+///
+/// [`Error::game_index`]: struct.Error.html#method.game_index
+/// [`Error::line`]: struct.Error.html#method.line
+pub struct PgnReader<R, P> {
+    games: GameTexts<R>,
+    stopped: bool,
+    phantom: PhantomData<fn() -> P>,
+}
+
+impl<R: BufRead, P> PgnReader<R, P> {
+    pub fn new(reader: R) -> Self {
+        PgnReader {
+            games: GameTexts::new(reader),
+            stopped: false,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over only the games whose tags match `predicate`.
+    ///
+    /// The predicate is called with the tags of each game as they appear in the input, before the movetext is parsed.
+    /// The movetext of games that do not match is skipped without being parsed, which is much faster than parsing
+    /// every game and filtering afterwards. Games with invalid tags are always parsed, so that their error is yielded.
+    pub fn filter_tags<F>(self, predicate: F) -> FilterTags<R, P, F>
+    where
+        F: FnMut(&Tags) -> bool,
+    {
+        FilterTags {
+            reader: self,
+            predicate,
+        }
+    }
+
+    /// Returns the next game whose tags match `predicate`
+    fn next_matching<F>(&mut self, mut predicate: F) -> Option<Result<PgnGame<P>, Error>>
+    where
+        P: PgnPosition + Clone,
+        F: FnMut(&Tags) -> bool,
+    {
+        while !self.stopped {
+            match self.games.next()? {
+                Ok(text) => {
+                    if let Some(tags) = raw_tags(&text) {
+                        if !predicate(&tags) {
+                            continue;
+                        }
+                    }
+                    return Some(
+                        build_game(&text).map_err(|err| self.games.locate_error(&text, err)),
+                    );
+                }
+                Err(err) => {
+                    if err.kind == ErrorKind::IoError {
+                        self.stopped = true;
+                    }
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, P> PgnReader<Cursor<Cow<'a, [u8]>>, P> {
+    /// Returns a reader for the games in an in-memory buffer with the given encoding.
+    ///
+    /// UTF-8 input is read directly from `bytes`, so games with invalid UTF-8 yield an `EncodingError`
+    /// with the span of the invalid bytes, like with [`new`]. Latin-1 input is decoded up front,
+    /// and the spans of its errors are offsets in the decoded UTF-8 text.
+    ///
+    /// [`new`]: #method.new
+    pub fn from_bytes(bytes: &'a [u8], encoding: Encoding) -> Self {
+        let input = match encoding {
+            Encoding::Utf8 => Cow::Borrowed(bytes),
+            Encoding::Latin1 => Cow::Owned(decode_latin1(bytes).into_bytes()),
+        };
+        PgnReader::new(Cursor::new(input))
+    }
+}
+
+/// Parses only the tags of a game's text, or returns `None` if they are invalid
+fn raw_tags(text: &str) -> Option<Tags> {
+    let mut tags = Tags::new();
+    for tag in raw_games(text).next()?.ok()?.tags() {
+        let (name, value) = tag.ok()?;
+        tags.set(name, &value);
+    }
+    Some(tags)
+}
+
+impl<R: BufRead, P: PgnPosition + Clone> Iterator for PgnReader<R, P> {
+    type Item = Result<PgnGame<P>, Error>;
+
+    fn next(&mut self) -> Option<Result<PgnGame<P>, Error>> {
+        self.next_matching(|_| true)
+    }
+}
+
+/// An iterator over the games of a pgn file whose tags match a predicate.
+///
+/// This struct is created by [`PgnReader::filter_tags`].
+///
+/// [`PgnReader::filter_tags`]: struct.PgnReader.html#method.filter_tags
+pub struct FilterTags<R, P, F> {
+    reader: PgnReader<R, P>,
+    predicate: F,
+}
+
+impl<R, P, F> Iterator for FilterTags<R, P, F>
+where
+    R: BufRead,
+    P: PgnPosition + Clone,
+    F: FnMut(&Tags) -> bool,
+{
+    type Item = Result<PgnGame<P>, Error>;
+
+    fn next(&mut self) -> Option<Result<PgnGame<P>, Error>> {
+        self.reader.next_matching(&mut self.predicate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPosition;
+
+    fn read_games(input: &str) -> Vec<Result<PgnGame<MockPosition>, Error>> {
+        PgnReader::new(input.as_bytes()).collect()
+    }
+
+    #[test]
+    fn games_without_tags_after_the_first_are_errors() {
+        let games = read_games("1. e4 e5 *\n\n1. d4 *\n\n1. Nf3 *\n");
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].as_ref().unwrap_err().kind(), ErrorKind::ParseError);
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        let games = read_games("\u{feff}[Event \"1\"]\n1. e4 *\n\n[Event \"2\"]\n1. d4 *\n");
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].as_ref().unwrap().tags.get("Event"), Some("1"));
+    }
+
+    #[test]
+    fn errors_have_their_location_in_the_input() {
+        let input = "\u{feff}[Event \"1\"]\n1. e4 *\n\n[Event \"2\"]\n1. e4\nKe2 *\n\n[Event \"3\"]\n1. d4 *\n";
+        let games = read_games(input);
+        assert_eq!(games.len(), 3);
+        assert!(games[0].is_ok() && games[2].is_ok());
+
+        let err = games[1].as_ref().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert_eq!(err.game_index(), Some(1));
+        assert_eq!(err.line(), Some(6));
+        assert_eq!(&input[err.span().unwrap()], "Ke2");
+        assert!(err
+            .to_string()
+            .starts_with("Illegal move. Failed to parse game 2, at line 6"));
+    }
+
+    #[test]
+    fn games_after_invalid_utf8_have_their_location() {
+        let input = b"[Event \"1\"]\n1. e4 *\n\n[Event \"2\"]\n1. e4 \xFF *\n\n[Event \"3\"]\n1. e4 Ke2 *\n";
+        let games: Vec<Result<PgnGame<MockPosition>, Error>> = PgnReader::new(&input[..]).collect();
+        assert_eq!(games.len(), 3);
+        assert!(games[0].is_ok());
+
+        let err = games[1].as_ref().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EncodingError);
+        assert_eq!(err.game_index(), Some(1));
+        assert_eq!(err.line(), Some(5));
+        assert_eq!(&input[err.span().unwrap()], b"\xFF");
+
+        let err = games[2].as_ref().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert_eq!(err.game_index(), Some(2));
+        assert_eq!(err.line(), Some(8));
+        assert_eq!(&input[err.span().unwrap()], b"Ke2");
+
+        // An invalid line that starts a game belongs to that game
+        let input = b"1. e4 *\n\n[Event \"\xFF\"]\n1. d4 *\n";
+        let games: Vec<Result<PgnGame<MockPosition>, Error>> = PgnReader::new(&input[..]).collect();
+        assert!(games[0].is_ok());
+        assert_eq!(games[1].as_ref().unwrap_err().game_index(), Some(1));
+        assert_eq!(games[1].as_ref().unwrap_err().line(), Some(3));
+    }
+
+    #[test]
+    fn filter_games_by_tags() {
+        let input = "[White \"A\"]\n1. e4 {A comment\n[White \"B\"]\n} e5 *\n\n\
+                     [White \"B\"]\n1. e4 (1. d4 {Not parsed} Ke2) Ke2 *\n\n\
+                     [White \"A\"]\n1. d4 *\n\n\
+                     [White \"A\"]\n1. Ke2 *\n";
+        let games: Vec<Result<PgnGame<MockPosition>, Error>> = PgnReader::new(input.as_bytes())
+            .filter_tags(|tags| tags.white() == Some("A"))
+            .collect();
+        assert_eq!(games.len(), 3);
+        assert_eq!(
+            games[0].as_ref().unwrap().moves[0].comment.as_deref(),
+            Some("A comment\n[White \"B\"]\n")
+        );
+        assert_eq!(games[1].as_ref().unwrap().moves[0].mv, "d4");
+
+        // Errors in matching games keep their location in the whole input
+        let err = games[2].as_ref().unwrap_err();
+        assert_eq!(err.game_index(), Some(3));
+        assert_eq!(err.line(), Some(13));
+    }
+
+    #[test]
+    fn read_games_from_latin1_bytes() {
+        let input = b"[White \"Ma\xEFs\"]\n1. e4 *\n\n[White \"\xC5se\"]\n1. Ke2 *\n";
+        let games: Vec<Result<PgnGame<MockPosition>, Error>> =
+            PgnReader::from_bytes(input, Encoding::Latin1).collect();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].as_ref().unwrap().tags.white(), Some("Maïs"));
+        let err = games[1].as_ref().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert_eq!(err.game_index(), Some(1));
+        assert_eq!(err.line(), Some(5));
+
+        // The same bytes are not valid UTF-8
+        let games: Vec<Result<PgnGame<MockPosition>, Error>> =
+            PgnReader::from_bytes(input, Encoding::Utf8).collect();
+        assert_eq!(games.len(), 2);
+        let err = games[0].as_ref().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EncodingError);
+        assert_eq!(&input[err.span().unwrap()], b"\xEF");
+    }
+}
@@ -0,0 +1,85 @@
+//! The structured value of the `Round` tag.
+
+use std::fmt;
+
+/// The value of a pgn `Round` tag.
+///
+/// Rounds can be hierarchical, like `3.1.2` for the second game of the first board in round 3.
+/// Converting a `Round` back to a string always reproduces the original tag value.
+#[derive(Clone, Eq, PartialEq, Debug, PartialOrd, Ord, Hash)]
+pub enum Round {
+    /// The round is unknown, written as `?`
+    Unknown,
+    /// Rounds do not apply to the game, written as `-`
+    NotApplicable,
+    /// The dot-separated components of the round
+    Numbered(Vec<u32>),
+    /// A round value that does not follow the standard format
+    Other(String),
+}
+
+impl From<&str> for Round {
+    fn from(round: &str) -> Round {
+        match round {
+            "?" => Round::Unknown,
+            "-" => Round::NotApplicable,
+            _ => round
+                .split('.')
+                .map(|component| {
+                    // Leading zeros or signs would not survive a round-trip
+                    let is_canonical = !component.is_empty()
+                        && component.chars().all(|c| c.is_ascii_digit())
+                        && (component == "0" || !component.starts_with('0'));
+                    if is_canonical {
+                        component.parse().ok()
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Option<Vec<u32>>>()
+                .map_or_else(|| Round::Other(round.to_string()), Round::Numbered),
+        }
+    }
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Round::Unknown => write!(fmt, "?"),
+            Round::NotApplicable => write!(fmt, "-"),
+            Round::Numbered(components) => {
+                for (i, component) in components.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, ".")?;
+                    }
+                    write!(fmt, "{}", component)?;
+                }
+                Ok(())
+            }
+            Round::Other(round) => write!(fmt, "{}", round),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rounds() {
+        assert_eq!(Round::from("?"), Round::Unknown);
+        assert_eq!(Round::from("-"), Round::NotApplicable);
+        assert_eq!(Round::from("3"), Round::Numbered(vec![3]));
+        assert_eq!(Round::from("3.1.2"), Round::Numbered(vec![3, 1, 2]));
+        assert_eq!(Round::from("03"), Round::Other("03".to_string()));
+        assert_eq!(Round::from("3."), Round::Other("3.".to_string()));
+        assert_eq!(Round::from("Final"), Round::Other("Final".to_string()));
+    }
+
+    #[test]
+    fn rounds_round_trip() {
+        for round in ["?", "-", "0", "3.1.2", "03", "+1", "Final", ""] {
+            assert_eq!(Round::from(round).to_string(), round);
+        }
+    }
+}
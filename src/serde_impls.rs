@@ -0,0 +1,100 @@
+//! Serialization of types that do not implement serde's traits themselves, for use with `#[serde(with = "...")]`.
+
+/// Serializes a position as its FEN
+pub(crate) mod fen {
+    use crate::PgnPosition;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<P, S>(position: &P, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        P: PgnPosition,
+        S: Serializer,
+    {
+        serializer.serialize_str(&position.to_fen())
+    }
+
+    pub(crate) fn deserialize<'de, P, D>(deserializer: D) -> Result<P, D::Error>
+    where
+        P: PgnPosition,
+        D: Deserializer<'de>,
+    {
+        let fen = String::deserialize(deserializer)?;
+        P::from_fen(&fen).map_err(D::Error::custom)
+    }
+}
+
+/// Serializes a game result as the name of its variant, or `None` for unfinished games
+pub(crate) mod game_result {
+    use board_game_traits::GameResult;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S>(
+        result: &Option<GameResult>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let name = result.map(|result| match result {
+            GameResult::WhiteWin => "WhiteWin",
+            GameResult::BlackWin => "BlackWin",
+            GameResult::Draw => "Draw",
+        });
+        name.serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<GameResult>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(deserializer)?.as_deref() {
+            None => Ok(None),
+            Some("WhiteWin") => Ok(Some(GameResult::WhiteWin)),
+            Some("BlackWin") => Ok(Some(GameResult::BlackWin)),
+            Some("Draw") => Ok(Some(GameResult::Draw)),
+            Some(name) => Err(D::Error::unknown_variant(
+                name,
+                &["WhiteWin", "BlackWin", "Draw"],
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::MockPosition;
+    use crate::{parse_pgn, PgnDate, PgnGame, Tags};
+
+    #[test]
+    fn game_round_trips_through_json() {
+        let game = parse_pgn::<MockPosition>(
+            "[Event \"Test\"]\n[SetUp \"1\"]\n[FEN \"start e4\"]\n[Result \"1-0\"]\n\n\
+             {Start} 1... e5 {[%eval 0.3] Good} (1... d5 $1) 2. Qh5 e5 3. mate 1-0",
+        )
+        .unwrap();
+        // The mock position's moves are `&'static str`, so they can only be borrowed from static input
+        let json: &'static str = Box::leak(serde_json::to_string(&game).unwrap().into_boxed_str());
+        assert!(json.contains("\"start_position\":\"start e4\""), "{}", json);
+        assert!(json.contains("\"result\":\"WhiteWin\""), "{}", json);
+        let deserialized: PgnGame<MockPosition> = serde_json::from_str(json).unwrap();
+        assert_eq!(deserialized, game);
+    }
+
+    #[test]
+    fn tags_and_dates_round_trip_through_json() {
+        let mut tags = Tags::new();
+        tags.set("White", "A \"quoted\" name");
+        tags.set("Event", "Test");
+        let json = serde_json::to_string(&tags).unwrap();
+        assert_eq!(json, r#"[["White","A \"quoted\" name"],["Event","Test"]]"#);
+        assert_eq!(serde_json::from_str::<Tags>(&json).unwrap(), tags);
+
+        for date in &["2024.03.17", "2024.??.??", "????.??.??"] {
+            let date: PgnDate = date.parse().unwrap();
+            let json = serde_json::to_string(&date).unwrap();
+            assert_eq!(serde_json::from_str::<PgnDate>(&json).unwrap(), date);
+        }
+    }
+}
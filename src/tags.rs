@@ -0,0 +1,127 @@
+//! The tag pairs of a pgn game.
+
+use crate::PgnDate;
+use std::collections::HashMap;
+
+/// The tag pairs of a pgn game, in the order they were added.
+///
+/// Tag names are case-sensitive, and are looked up in constant time.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Tags {
+    pairs: Vec<(String, String)>,
+    /// The index of each tag name in `pairs`
+    indices: HashMap<String, usize>,
+}
+
+impl Tags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of the tag with exactly this name, if there is one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.indices
+            .get(name)
+            .map(|&index| self.pairs[index].1.as_str())
+    }
+
+    /// Sets the value of a tag.
+    ///
+    /// If the tag already exists, its value is replaced and it keeps its position. Otherwise, it is added last.
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.indices.get(name) {
+            Some(&index) => self.pairs[index].1 = value.to_string(),
+            None => {
+                self.indices.insert(name.to_string(), self.pairs.len());
+                self.pairs.push((name.to_string(), value.to_string()));
+            }
+        }
+    }
+
+    /// Returns an iterator over the names and values of the tags, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// The value of the `White` tag
+    pub fn white(&self) -> Option<&str> {
+        self.get("White")
+    }
+
+    /// The value of the `Black` tag
+    pub fn black(&self) -> Option<&str> {
+        self.get("Black")
+    }
+
+    /// The value of the `Result` tag
+    pub fn result(&self) -> Option<&str> {
+        self.get("Result")
+    }
+
+    /// The value of the `Date` tag in structured form, or `None` if the tag is missing or malformed
+    pub fn date(&self) -> Option<PgnDate> {
+        self.get("Date")?.parse().ok()
+    }
+}
+
+/// Tags are serialized as a sequence of name and value pairs, in order
+#[cfg(feature = "serde")]
+impl serde::Serialize for Tags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Tags {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pairs = Vec::<(String, String)>::deserialize(deserializer)?;
+        let mut tags = Tags::new();
+        for (name, value) in pairs.iter() {
+            tags.set(name, value);
+        }
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_keep_their_order() {
+        let mut tags = Tags::new();
+        tags.set("Event", "Test");
+        tags.set("White", "A");
+        tags.set("Result", "*");
+        tags.set("White", "B");
+        assert_eq!(
+            tags.iter().collect::<Vec<_>>(),
+            vec![("Event", "Test"), ("White", "B"), ("Result", "*")]
+        );
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags.white(), Some("B"));
+        assert_eq!(tags.black(), None);
+        assert_eq!(tags.result(), Some("*"));
+    }
+
+    #[test]
+    fn structured_date() {
+        let mut tags = Tags::new();
+        assert_eq!(tags.date(), None);
+        tags.set("Date", "2024.??.??");
+        assert_eq!(tags.date().unwrap().year, Some(2024));
+        tags.set("Date", "yesterday");
+        assert_eq!(tags.date(), None);
+    }
+}
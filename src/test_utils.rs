@@ -0,0 +1,238 @@
+//! Reusable assertions for testing implementations of [`PgnPosition`].
+//!
+//! [`PgnPosition`]: ../trait.PgnPosition.html
+
+use crate::PgnPosition;
+
+/// Asserts that converting each FEN to a position and back is idempotent.
+///
+/// Each FEN is parsed, written with `to_fen` and parsed again. The two positions must be equal,
+/// and the written FEN must be identical to the input, meaning that the input is in canonical form.
+///
+/// # Panics
+///
+/// Panics on the first FEN that fails to parse, or is not idempotent.
+pub fn assert_fen_idempotent<P: PgnPosition>(fens: &[&str]) {
+    for fen in fens {
+        let position = P::from_fen(fen)
+            .unwrap_or_else(|err| panic!("Failed to parse fen \"{}\": {}", fen, err));
+        let written_fen = position.to_fen();
+        let reparsed_position = P::from_fen(&written_fen).unwrap_or_else(|err| {
+            panic!(
+                "Failed to parse fen \"{}\", written from \"{}\": {}",
+                written_fen, fen, err
+            )
+        });
+        assert!(
+            position == reparsed_position,
+            "Fen \"{}\" was written as \"{}\", which parses to a different position",
+            fen,
+            written_fen
+        );
+        assert!(
+            *fen == written_fen,
+            "Fen \"{}\" is not canonical, it was written as \"{}\"",
+            fen,
+            written_fen
+        );
+    }
+}
+
+/// Asserts that the position's SAN agrees with a reference, for cases of `(fen, lan, expected_san)`.
+///
+/// For each case, the move is parsed from long algebraic notation in the position,
+/// `move_to_san` must return exactly `expected_san`, and `move_from_san` must parse `expected_san` back to the same move.
+///
+/// # Panics
+///
+/// Panics if any case fails, listing every failing case rather than just the first.
+pub fn assert_san_matches_reference<P: PgnPosition>(cases: &[(&str, &str, &str)]) {
+    let mut mismatches = vec![];
+    let mut failed_cases = 0;
+    for (fen, lan, expected_san) in cases {
+        let previous_mismatches = mismatches.len();
+        let position = match P::from_fen(fen) {
+            Ok(position) => position,
+            Err(err) => {
+                mismatches.push(format!("Failed to parse fen \"{}\": {}", fen, err));
+                failed_cases += 1;
+                continue;
+            }
+        };
+        let mv = match position.move_from_lan(lan) {
+            Ok(mv) => mv,
+            Err(err) => {
+                mismatches.push(format!(
+                    "Failed to parse move {} in \"{}\": {}",
+                    lan, fen, err
+                ));
+                failed_cases += 1;
+                continue;
+            }
+        };
+        let san = position.move_to_san(&mv);
+        if san != *expected_san {
+            mismatches.push(format!(
+                "Move {} in \"{}\" was written as {}, expected {}",
+                lan, fen, san, expected_san
+            ));
+        }
+        match position.move_from_san(expected_san) {
+            Ok(parsed_move) if parsed_move == mv => (),
+            Ok(parsed_move) => mismatches.push(format!(
+                "{} in \"{}\" was parsed as {:?}, expected {:?}",
+                expected_san, fen, parsed_move, mv
+            )),
+            Err(err) => mismatches.push(format!(
+                "Failed to parse {} in \"{}\": {}",
+                expected_san, fen, err
+            )),
+        }
+        if mismatches.len() > previous_mismatches {
+            failed_cases += 1;
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{} of {} cases failed:\n{}",
+        failed_cases,
+        cases.len(),
+        mismatches.join("\n")
+    );
+}
+
+/// Asserts that castling moves written as king moves in long algebraic notation are parsed, for cases of `(fen, lan, expected_san)`.
+///
+/// In chess, castling is written as the king's two-square move, like `e1g1` or `e1c1`,
+/// and in Chess960 as the king capturing its own rook, like `e1h1`.
+/// For each case, `move_from_lan` and `move_from_uci` must parse the same legal move, and `move_to_san` must write it as `expected_san`.
+///
+/// # Panics
+///
+/// Panics if any case fails, listing every failing case rather than just the first.
+pub fn assert_castling_from_king_moves<P: PgnPosition>(cases: &[(&str, &str, &str)]) {
+    let mut mismatches = vec![];
+    for (fen, lan, expected_san) in cases {
+        let position = match P::from_fen(fen) {
+            Ok(position) => position,
+            Err(err) => {
+                mismatches.push(format!("Failed to parse fen \"{}\": {}", fen, err));
+                continue;
+            }
+        };
+        let mv = match position.move_from_lan(lan) {
+            Ok(mv) => mv,
+            Err(err) => {
+                mismatches.push(format!(
+                    "Failed to parse castling move {} in \"{}\": {}",
+                    lan, fen, err
+                ));
+                continue;
+            }
+        };
+        let mut legal_moves = vec![];
+        position.generate_moves(&mut legal_moves);
+        let san = position.move_to_san(&mv);
+        if !legal_moves.contains(&mv) {
+            mismatches.push(format!(
+                "Castling move {} in \"{}\" was parsed as {:?}, which is not legal",
+                lan, fen, mv
+            ));
+        } else if san != *expected_san {
+            mismatches.push(format!(
+                "Castling move {} in \"{}\" was written as {}, expected {}",
+                lan, fen, san, expected_san
+            ));
+        }
+        match position.move_from_uci(lan) {
+            Ok(uci_move) if uci_move == mv => (),
+            Ok(uci_move) => mismatches.push(format!(
+                "Castling move {} in \"{}\" was parsed from uci as {:?}, expected {:?}",
+                lan, fen, uci_move, mv
+            )),
+            Err(err) => mismatches.push(format!(
+                "Failed to parse castling move {} in \"{}\" from uci: {}",
+                lan, fen, err
+            )),
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{} castling case(s) failed:\n{}",
+        mismatches.len(),
+        mismatches.join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPosition;
+
+    #[test]
+    fn castling_from_king_moves() {
+        assert_castling_from_king_moves::<MockPosition>(&[
+            ("start", "e1g1", "O-O"),
+            ("start", "e1c1", "O-O-O"),
+            ("start", "e1h1", "O-O"),
+            ("start", "e1a1", "O-O-O"),
+            ("start e4", "e8g8", "O-O"),
+            ("start e4", "e8c8", "O-O-O"),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "2 castling case(s) failed")]
+    fn wrong_castling_moves_are_reported() {
+        assert_castling_from_king_moves::<MockPosition>(&[
+            ("start", "e1g1", "O-O-O"),
+            ("start", "e1g1", "O-O"),
+            ("start e4", "e1g1", "O-O"),
+        ]);
+    }
+
+    #[test]
+    fn canonical_fens_are_idempotent() {
+        assert_fen_idempotent::<MockPosition>(&[
+            "start",
+            "start e4 e5 Nf3",
+            "nonull",
+            "start e4 null",
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Fen \"start e4!\" is not canonical, it was written as \"start e4\"")]
+    fn non_canonical_fens_are_reported() {
+        assert_fen_idempotent::<MockPosition>(&["start e4", "start e4!"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to parse fen \"invalid\"")]
+    fn invalid_fens_are_reported() {
+        assert_fen_idempotent::<MockPosition>(&["invalid"]);
+    }
+
+    #[test]
+    fn san_matches_reference() {
+        assert_san_matches_reference::<MockPosition>(&[
+            ("start", "e4", "e4"),
+            ("start e4", "Nf6", "Nf6"),
+            ("start e4 e5", "Qh5", "Qh5+"),
+            ("start", "e1g1", "O-O"),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "2 of 3 cases failed:\n\
+                               Move e4 in \"start\" was written as e4, expected d4\n\
+                               d4 in \"start\" was parsed as \"d4\", expected \"e4\"\n\
+                               Failed to parse move xx in \"start\": Illegal move. Unknown move xx")]
+    fn san_mismatches_are_all_reported() {
+        assert_san_matches_reference::<MockPosition>(&[
+            ("start", "e4", "d4"),
+            ("start", "d4", "d4"),
+            ("start", "xx", "xx"),
+        ]);
+    }
+}
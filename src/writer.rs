@@ -0,0 +1,414 @@
+//! Writing games as pgn.
+
+use crate::{AnnotatedMove, CommentCommands, Error, ErrorKind, PgnGame, PgnPosition, Tags};
+use board_game_traits::Color;
+use std::io;
+use std::io::Write;
+
+/// The maximum length of a movetext line
+const MAX_LINE_LENGTH: usize = 80;
+
+/// Writes a game as pgn in export format.
+///
+/// The position's `REQUIRED_TAGS` are written first and in order, followed by the game's other tags in their original order.
+/// The movetext includes comments, Numeric Annotation Glyphs and variations, and is wrapped at 80 columns.
+/// As required by the export format, a move by the second player is written with its move number, like `2...`,
+/// when it follows a comment or a variation.
+/// A comment after a variation belongs to the move before the variation, so it is written before the variation:
+/// `1. e4 e5 (1... d5) {After} 2. Nf3` is written as `1. e4 e5 {After} (1... d5) 2. Nf3`.
+///
+/// Tag values cannot contain newlines or other control characters in pgn, so a game with such a tag value
+/// is rejected with an `InvalidInput` error, before anything is written.
+pub fn write_pgn<P, W>(game: &PgnGame<P>, out: &mut W) -> io::Result<()>
+where
+    P: PgnPosition + Clone,
+    W: Write,
+{
+    if let Some((name, _)) = game
+        .tags
+        .iter()
+        .find(|(_, value)| value.chars().any(char::is_control))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("The value of tag {} contains a control character", name),
+        ));
+    }
+    for (name, default_value) in P::REQUIRED_TAGS {
+        let value = game.tags.get(name).unwrap_or(default_value);
+        write_tag(out, name, value)?;
+    }
+    for (name, value) in game.tags.iter() {
+        if !P::REQUIRED_TAGS
+            .iter()
+            .any(|(required_name, _)| *required_name == name)
+        {
+            write_tag(out, name, value)?;
+        }
+    }
+    writeln!(out)?;
+
+    let mut tokens = game_tokens(game);
+    tokens.push(P::result_to_str(game.result).to_string());
+    writeln!(out, "{}", wrap_tokens(&tokens))
+}
+
+/// Writes a game given as a start position and a list of moves in uci notation, like the output of a chess engine, as pgn.
+///
+/// The moves are resolved with `move_from_uci`, and written in standard algebraic notation with [`write_pgn`].
+/// The `SetUp` tag and the tag with the start position are added if the game does not start from the default start position.
+/// The result and the `Result` tag are set if the final position has a result, and are unfinished otherwise.
+/// Returns an `IllegalMove` error with the move's ply if a move is invalid or illegal.
+pub fn pgn_from_uci<P>(start_fen: &str, uci_moves: &[&str], mut tags: Tags) -> Result<String, Error>
+where
+    P: PgnPosition + Clone,
+{
+    let start_position = P::from_fen(start_fen)?;
+    let mut position = start_position.clone();
+    let mut moves = Vec::with_capacity(uci_moves.len());
+    for (ply, uci_move) in uci_moves.iter().enumerate() {
+        let illegal_move = |source: Option<Error>| {
+            let message = format!("Illegal uci move {} at ply {}", uci_move, ply);
+            match source {
+                Some(err) => Error::new_caused_by(ErrorKind::IllegalMove, message, err),
+                None => Error::new(ErrorKind::IllegalMove, message),
+            }
+        };
+        let mv = position
+            .move_from_uci(uci_move)
+            .map_err(|err| illegal_move(Some(err)))?;
+        let mut legal_moves = vec![];
+        position.generate_moves(&mut legal_moves);
+        if !legal_moves.contains(&mv) {
+            return Err(illegal_move(None));
+        }
+        position.do_move(mv.clone());
+        moves.push(AnnotatedMove {
+            mv,
+            comment: None,
+            commands: CommentCommands::default(),
+            nags: vec![],
+            variations: vec![],
+        });
+    }
+
+    if let Some(tag_name) = P::START_POSITION_TAG_NAME {
+        if start_position != P::start_position() {
+            tags.set("SetUp", "1");
+            tags.set(tag_name, &start_position.to_fen());
+        }
+    }
+    let result = position.game_result();
+    tags.set("Result", P::result_to_str(result));
+    let game = PgnGame {
+        tags,
+        start_position,
+        comment: None,
+        moves,
+        result,
+    };
+
+    let mut output = vec![];
+    write_pgn(&game, &mut output)
+        .map_err(|err| Error::new_caused_by(ErrorKind::IoError, "Failed to write pgn", err))?;
+    Ok(String::from_utf8(output).expect("The pgn writer only writes valid UTF-8"))
+}
+
+/// Returns the game's movetext as written by [`write_pgn`], without the result
+pub(crate) fn movetext<P>(game: &PgnGame<P>) -> String
+where
+    P: PgnPosition + Clone,
+{
+    wrap_tokens(&game_tokens(game))
+}
+
+/// Returns the tokens for the game's comment and main line, including annotations and variations
+fn game_tokens<P>(game: &PgnGame<P>) -> Vec<String>
+where
+    P: PgnPosition + Clone,
+{
+    let mut tokens = vec![];
+    if let Some(comment) = &game.comment {
+        tokens.push(format!("{{{}}}", comment));
+    }
+    let move_number = game.start_position.full_move_number().unwrap_or(1);
+    movetext_tokens(&game.start_position, &game.moves, move_number, &mut tokens);
+    tokens
+}
+
+/// Joins tokens with spaces, wrapping lines at `MAX_LINE_LENGTH` columns where possible
+fn wrap_tokens(tokens: &[String]) -> String {
+    let mut output = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 {
+            if line_length + 1 + token.len() > MAX_LINE_LENGTH {
+                output.push('\n');
+                line_length = 0;
+            } else {
+                output.push(' ');
+                line_length += 1;
+            }
+        }
+        output.push_str(token);
+        line_length = match token.rfind('\n') {
+            Some(i) => token.len() - i - 1,
+            None => line_length + token.len(),
+        };
+    }
+    output
+}
+
+fn write_tag<W: Write>(out: &mut W, name: &str, value: &str) -> io::Result<()> {
+    writeln!(
+        out,
+        "[{} \"{}\"]",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// Appends the tokens for a line of moves starting in `position`, including its annotations and variations
+fn movetext_tokens<P>(
+    position: &P,
+    moves: &[AnnotatedMove<P>],
+    mut move_number: u32,
+    tokens: &mut Vec<String>,
+) where
+    P: PgnPosition + Clone,
+{
+    let mut position = position.clone();
+    // The second player's moves only need a move number at the start of a line, or after a comment or variation
+    let mut needs_move_number = true;
+
+    for annotated_move in moves {
+        match position.side_to_move() {
+            Color::White => tokens.push(format!("{}.", move_number)),
+            Color::Black if needs_move_number => tokens.push(format!("{}...", move_number)),
+            Color::Black => (),
+        }
+        if position.null_move().as_ref() == Some(&annotated_move.mv) {
+            tokens.push("--".to_string());
+        } else {
+            tokens.push(position.move_to_san(&annotated_move.mv));
+        }
+        needs_move_number = false;
+
+        for nag in annotated_move.nags.iter() {
+            tokens.push(format!("${}", nag));
+        }
+        if annotated_move.comment.is_some() || !annotated_move.commands.is_empty() {
+            let comment = annotated_move
+                .commands
+                .to_comment(annotated_move.comment.as_deref());
+            tokens.push(format!("{{{}}}", comment));
+            needs_move_number = true;
+        }
+        for variation in annotated_move.variations.iter() {
+            let variation_start = tokens.len();
+            if let Some(comment) = &variation.comment {
+                tokens.push(format!("{{{}}}", comment));
+            }
+            movetext_tokens(&position, &variation.moves, move_number, tokens);
+            if tokens.len() == variation_start {
+                tokens.push("()".to_string());
+            } else {
+                tokens[variation_start].insert(0, '(');
+                tokens.last_mut().unwrap().push(')');
+            }
+            needs_move_number = true;
+        }
+
+        if position.side_to_move() == Color::Black {
+            move_number += 1;
+        }
+        position.do_move(annotated_move.mv.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{write, MockPosition, NULL_MOVE};
+    use crate::parse_pgn;
+    use board_game_traits::GameResult;
+
+    #[test]
+    fn null_moves_in_variations() {
+        let game = parse_pgn::<MockPosition>("1. e4 e5 (1... -- 2. d4 Z0) 2. Nf3 *").unwrap();
+        let variation = &game.moves[1].variations[0];
+        let moves: Vec<_> = variation.moves.iter().map(|mv| mv.mv).collect();
+        assert_eq!(moves, vec![NULL_MOVE, "d4", NULL_MOVE]);
+        assert_eq!(
+            write(&game).lines().last(),
+            Some("1. e4 e5 (1... -- 2. d4 --) 2. Nf3 *")
+        );
+
+        let input = "[SetUp \"1\"]\n[FEN \"nonull\"]\n\n1. e4 e5 (1... -- 2. d4) *";
+        let err = parse_pgn::<MockPosition>(input).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert_eq!(&input[err.span().unwrap()], "--");
+    }
+
+    #[test]
+    fn unusual_tag_names_round_trip() {
+        let tags = "[WhiteRatingDiff \"+5\"]\n[UTCTime \"12:00:00\"]\n[My_Key2 \"a\"]\n\
+                    [Tag.With.Dots \"b\"]\n[Åpning \"c\"]\n[white \"lowercase\"]\n";
+        let game = parse_pgn::<MockPosition>(&format!("{}\n1. e4 *", tags)).unwrap();
+        assert_eq!(game.tags.get("WhiteRatingDiff"), Some("+5"));
+        assert_eq!(game.tags.get("Tag.With.Dots"), Some("b"));
+        assert_eq!(game.tags.get("Åpning"), Some("c"));
+        // Tag names are case sensitive
+        assert_eq!(game.tags.get("white"), Some("lowercase"));
+        assert_eq!(game.tags.white(), Some("?"));
+
+        let output = write(&game);
+        assert_eq!(
+            output,
+            format!(
+                "[Event \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n{}\n1. e4 *\n",
+                tags
+            )
+        );
+        assert_eq!(parse_pgn::<MockPosition>(&output).unwrap().tags.len(), 10);
+    }
+
+    #[test]
+    fn multi_paragraph_comments_round_trip() {
+        let movetext = "1. e4 e5 {First paragraph.\n\n  Second paragraph, indented.\n} 2. Nf3 *\n";
+        let game = parse_pgn::<MockPosition>(movetext).unwrap();
+        assert_eq!(
+            game.moves[1].comment.as_deref(),
+            Some("First paragraph.\n\n  Second paragraph, indented.\n")
+        );
+        let output = write(&game);
+        assert!(output.ends_with(&format!("\n\n{}", movetext)), "{}", output);
+    }
+
+    #[test]
+    fn write_game_in_export_format() {
+        let input = "[Site \"Oslo\"]\n[White \"Back\\\\slash \\\"A\\\"\"]\n[Result \"1-0\"]\n\n\
+                     {Start} 1. e4 e5 $1 {Comment} (1... d5 2. Nf3) 2. Qh5+ Nc6 3. mate# 1-0\n";
+        let game = parse_pgn::<MockPosition>(input).unwrap();
+        assert_eq!(
+            write(&game),
+            "[Event \"?\"]\n[White \"Back\\\\slash \\\"A\\\"\"]\n[Black \"?\"]\n[Result \"1-0\"]\n[Site \"Oslo\"]\n\n\
+             {Start} 1. e4 e5 $1 {Comment} (1... d5 2. Nf3) 2. Qh5+ Nc6 3. mate# 1-0\n"
+        );
+        // Writing reorders the tags, but is otherwise lossless
+        let reparsed_game = parse_pgn::<MockPosition>(&write(&game)).unwrap();
+        assert_eq!(reparsed_game.moves, game.moves);
+        assert_eq!(write(&reparsed_game), write(&game));
+    }
+
+    #[test]
+    fn movetext_is_wrapped_at_80_columns() {
+        let movetext = "1. e4 e5 2. Nf3 Nc6 ".repeat(10) + "*";
+        let game = parse_pgn::<MockPosition>(&movetext).unwrap();
+        let output = write(&game);
+        assert!(output.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(output.lines().count() > 6);
+        assert_eq!(parse_pgn::<MockPosition>(&output).unwrap(), game);
+    }
+
+    #[test]
+    fn movetext_without_tags_or_result() {
+        let input =
+            "[Result \"1-0\"]\n\n{Start} 1. e4 e5 $1 (1... d5 2. Nf3) 2. Qh5+ {Check} Nc6 1-0\n";
+        let game = parse_pgn::<MockPosition>(input).unwrap();
+        assert_eq!(
+            game.movetext(),
+            "{Start} 1. e4 e5 $1 (1... d5 2. Nf3) 2. Qh5+ {Check} 2... Nc6"
+        );
+        assert_eq!(parse_pgn::<MockPosition>("*").unwrap().movetext(), "");
+
+        let game = parse_pgn::<MockPosition>(&("1. e4 e5 2. Nf3 Nc6 ".repeat(10) + "*")).unwrap();
+        assert!(write(&game).ends_with(&format!("\n\n{} *\n", game.movetext())));
+    }
+
+    #[test]
+    fn tag_values_with_control_characters_are_rejected() {
+        for value in &["Oslo\n[Result \"1-0\"]", "Oslo\tNorway"] {
+            let mut game = parse_pgn::<MockPosition>("1. e4 *").unwrap();
+            game.tags.set("Site", value);
+            let mut output = vec![];
+            let err = write_pgn(&game, &mut output).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(
+                err.to_string(),
+                "The value of tag Site contains a control character"
+            );
+            assert!(output.is_empty());
+        }
+    }
+
+    #[test]
+    fn pgn_from_uci_moves() {
+        let mut tags = Tags::new();
+        tags.set("White", "Engine");
+        let pgn = pgn_from_uci::<MockPosition>("start", &["e4", "e5", "e1g1"], tags).unwrap();
+        assert_eq!(
+            pgn,
+            "[Event \"?\"]\n[White \"Engine\"]\n[Black \"?\"]\n[Result \"*\"]\n\n1. e4 e5 2. O-O *\n"
+        );
+
+        let pgn = pgn_from_uci::<MockPosition>("start e4", &["e5", "mate"], Tags::new()).unwrap();
+        assert_eq!(
+            pgn,
+            "[Event \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"1-0\"]\n[SetUp \"1\"]\n[FEN \"start e4\"]\n\n\
+             1... e5 2. mate# 1-0\n"
+        );
+        let game = parse_pgn::<MockPosition>(&pgn).unwrap();
+        assert_eq!(game.result, Some(GameResult::WhiteWin));
+    }
+
+    #[test]
+    fn pgn_from_uci_rejects_illegal_moves() {
+        let err = pgn_from_uci::<MockPosition>("start", &["e4", "e7e5"], Tags::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+        assert!(
+            err.to_string().contains("Illegal uci move e7e5 at ply 1"),
+            "{}",
+            err
+        );
+
+        // No moves are legal after the game has ended
+        let err = pgn_from_uci::<MockPosition>("start", &["mate", "e5"], Tags::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::IllegalMove);
+    }
+
+    #[test]
+    fn depth_and_pv_round_trip() {
+        let movetext = "1. e4 {[%eval 0.30] [%depth 18] [%pv e5 Nf3 Nc6] [%csl Ga4]} 1... e5 *";
+        let game = parse_pgn::<MockPosition>(movetext).unwrap();
+        assert_eq!(game.moves[0].commands.depth, Some(18));
+        assert_eq!(game.moves[0].commands.pv, vec!["e5", "Nf3", "Nc6"]);
+        assert_eq!(game.moves[0].comment.as_deref(), Some("[%csl Ga4]"));
+        assert_eq!(write(&game).lines().last(), Some(movetext));
+        assert_eq!(parse_pgn::<MockPosition>(&write(&game)).unwrap(), game);
+    }
+
+    #[test]
+    fn move_numbers_are_repeated_after_comments_and_variations() {
+        let input =
+            "{Start} 1. e4 {White} 1... e5 2. Nf3 (2. d4 {Center} 2... d5) 2... Nc6 {Black} \
+                     3. Bb5 a6 (3... Nf6) 4. O-O *";
+        let game = parse_pgn::<MockPosition>(input).unwrap();
+        assert_eq!(format!("{} *", game.movetext().replace('\n', " ")), input);
+        assert_eq!(parse_pgn::<MockPosition>(&write(&game)).unwrap(), game);
+
+        // Without the comments and variations, no move numbers are repeated
+        let game = parse_pgn::<MockPosition>("1. e4 1... e5 2. Nf3 2... Nc6 *").unwrap();
+        assert_eq!(write(&game).lines().last(), Some("1. e4 e5 2. Nf3 Nc6 *"));
+    }
+
+    #[test]
+    fn comments_after_variations_are_written_before_them() {
+        let game = parse_pgn::<MockPosition>("1. e4 e5 (1... d5) {After} 2. Nf3 *").unwrap();
+        assert_eq!(game.moves[1].comment.as_deref(), Some("After"));
+        assert_eq!(
+            write(&game).lines().last(),
+            Some("1. e4 e5 {After} (1... d5) 2. Nf3 *")
+        );
+    }
+}